@@ -0,0 +1,37 @@
+//! Grading helpers for Moodle assignments.
+//!
+//! The `tutors` binary is a thin wrapper around the commands exported here ([`count`],
+//! [`unzip`], [`fill_table`], [`zipit`], ...). The functions that return data instead of
+//! writing files ([`grade`], [`fill`], [`read_table`], ...) can be used to build other tools
+//! on the same grading logic.
+
+#[macro_use]
+mod tutorsmacros;
+mod tutors_archive;
+mod tutors_csv;
+mod tutors_deduction;
+mod tutors_error;
+mod tutors_feedback;
+mod tutors_gradebook;
+mod tutors_language;
+mod tutors_late;
+mod tutors_report;
+mod tutors_split;
+mod tutors_stats;
+mod tutorslib;
+
+pub use tutors_archive::{extract, ArchiveKind, Budget, Extracted, Limits};
+pub use tutors_csv::{read_table, write_table, Locale, Record, Rejected, Table};
+pub use tutors_deduction::{
+    calculate_deduction, tally, Deduction, Grading, Mode, Tally, TaskLimits,
+};
+pub use tutors_error::{Error, Problems};
+pub use tutors_gradebook::{Assignment, Gradebook, Student, Total, THRESHOLDS};
+pub use tutors_language::{CommentSyntax, Languages};
+pub use tutors_late::{parse_date, LatePolicy, Timestamp, Unit};
+pub use tutors_split::{assign, SplitOptions, Strategy, Submission};
+pub use tutorslib::{
+    check_layout, count, fill, fill_table, get_dirs, grade, gradebook_grades, gradebook_import,
+    gradebook_missing, gradebook_show, merge, merge_tables, moodle_id, orphans, render, split,
+    stats, sum_deduction, unzip, zip_dir, zipit, FillOptions, Graded, Unmatched,
+};
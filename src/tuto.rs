@@ -1,12 +1,9 @@
 use std::path::PathBuf;
 
 use anyhow::Result;
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
 
-#[macro_use]
-mod tutorsmacros;
-mod tutors_csv;
-mod tutorslib;
+mod tutors_config;
 
 #[derive(Parser)]
 #[command(author = "T. Pilz")]
@@ -17,6 +14,14 @@ struct Cli {
     #[arg(short, long, action = clap::ArgAction::SetTrue)]
     debug: bool,
 
+    /// Print the files that would be created, moved or deleted without changing anything
+    #[arg(long, global = true)]
+    dry_run: bool,
+
+    /// Config file to use instead of the nearest `tutors.toml`
+    #[arg(short, long, global = true)]
+    config: Option<PathBuf>,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -24,89 +29,641 @@ struct Cli {
 #[derive(Subcommand, Debug)]
 enum Commands {
     Zip {
-        /// Name of the feedback zip
-        #[arg(short, long, default_value = "feedback")]
-        name: String,
-        /// Directory containing all submissions and feedbacks
-        #[arg(default_value = ".")]
-        path: PathBuf,
+        /// Name of the feedback zip [default: feedback]
+        #[arg(short, long)]
+        name: Option<String>,
+        /// Directory containing all submissions and feedbacks [default: .]
+        path: Option<PathBuf>,
         /// Directory to store the resulting zip file [default: PATH/../]
         #[arg(short, long)]
         target_dir: Option<PathBuf>,
+        /// Grading table to check the submission folders against
+        #[arg(long)]
+        table: Option<PathBuf>,
+        /// Keep the feedback files and write the feedback zips only into the resulting zip
+        #[arg(short, long, action = clap::ArgAction::SetTrue, overrides_with = "no_keep")]
+        keep: bool,
+        /// Replace the feedback files by the feedback zips even if the config sets `keep`
+        #[arg(long, action = clap::ArgAction::SetTrue, overrides_with = "keep")]
+        no_keep: bool,
     },
-    /// Unzip outer and inner containers
+    /// Unzip outer and inner containers (zip, tar, tar.gz, tar.bz2, tar.xz and 7z)
     Unzip {
         path: PathBuf,
         /// Unzip only outermost zip
-        #[arg(short, long, action = clap::ArgAction::SetTrue)]
+        #[arg(short, long, action = clap::ArgAction::SetTrue, overrides_with = "no_single")]
         single: bool,
+        /// Unzip the inner archives even if the config sets `single`
+        #[arg(long, action = clap::ArgAction::SetTrue, overrides_with = "single")]
+        no_single: bool,
         /// Flatten the directory structure
-        #[arg(short, long, action = clap::ArgAction::SetTrue)]
+        #[arg(short, long, action = clap::ArgAction::SetTrue, overrides_with = "no_flatten")]
         flatten: bool,
+        /// Keep the directory structure even if the config sets `flatten`
+        #[arg(long, action = clap::ArgAction::SetTrue, overrides_with = "flatten")]
+        no_flatten: bool,
         /// Specify the target directory to unzip to [default: ./<FILE_NAME>]
         #[arg(short, long)]
         target: Option<PathBuf>,
+        /// Maximum nesting depth of archives inside a submission [default: 3]
+        #[arg(long)]
+        max_depth: Option<usize>,
+        /// Maximum uncompressed size of a single submission in MiB [default: 100]
+        #[arg(long, value_name = "MIB")]
+        max_size: Option<u64>,
+        /// Maximum ratio of extracted bytes to archive size, checked per archive above 1 MiB [default: 100]
+        #[arg(long)]
+        max_ratio: Option<f64>,
+        /// Maximum number of files and directories in a single submission [default: 10000]
+        #[arg(long)]
+        max_entries: Option<u64>,
     },
     Count {
-        #[arg(short, long, default_value = ".")]
-        path: PathBuf,
-        #[arg(short, long, default_value = ".")]
-        target_dir: PathBuf,
+        /// [default: .]
+        #[arg(short, long)]
+        path: Option<PathBuf>,
+        /// [default: .]
+        #[arg(short, long)]
+        target_dir: Option<PathBuf>,
         /// maximum points for the assignment [default = 25]
         #[arg(short, long)]
         max_points: Option<u8>,
+        #[command(flatten)]
+        grading: GradingArgs,
     },
     Fill {
         /// Path to the table file
+        table_path: Option<PathBuf>,
+        /// Path to the directory containing the student submissions [default: .]
+        #[arg(short, long)]
+        dir_path: Option<PathBuf>,
+        /// [default: result.csv]
+        #[arg(short, long)]
+        result_path: Option<PathBuf>,
+        #[command(flatten)]
+        grading: GradingArgs,
+        /// Write the feedback comment as HTML
+        #[arg(long, action = clap::ArgAction::SetTrue, overrides_with = "no_html")]
+        html: bool,
+        /// Write the feedback comment as plain text even if the config sets `html`
+        #[arg(long, action = clap::ArgAction::SetTrue, overrides_with = "html")]
+        no_html: bool,
+        /// What to do with students without a submission: drop, keep or zero [default: drop]
+        #[arg(long)]
+        unmatched: Option<tutors::Unmatched>,
+        #[command(flatten)]
+        late: LateArgs,
+        /// Grade submission folders without a row in the table and write their points to this CSV
+        #[arg(long, value_name = "CSV")]
+        orphans: Option<PathBuf>,
+        /// maximum points of the folders written to `--orphans` [default: the most common in the table]
+        #[arg(short, long)]
+        max_points: Option<u8>,
+        /// Abort if a row of the table can't be read instead of copying it unchanged
+        #[arg(long, action = clap::ArgAction::SetTrue, overrides_with = "no_strict")]
+        strict: bool,
+        /// Copy unreadable rows even if the config sets `strict`
+        #[arg(long, action = clap::ArgAction::SetTrue, overrides_with = "strict")]
+        no_strict: bool,
+        /// Overwrite grades that are already in the table; locked rows are never changed
+        #[arg(long, action = clap::ArgAction::SetTrue, overrides_with = "no_force")]
+        force: bool,
+        /// Keep grades that are already in the table even if the config sets `force`
+        #[arg(long, action = clap::ArgAction::SetTrue, overrides_with = "force")]
+        no_force: bool,
+    },
+    /// Print a summary of a graded table or a directory of submissions
+    Stats {
+        /// Path to a filled table or the directory containing the student submissions [default: .]
+        path: Option<PathBuf>,
+        /// maximum points for the assignment when reading submissions [default = 25]
+        #[arg(short, long)]
+        max_points: Option<u8>,
+        /// Minimum share of the maximum points needed to pass [default: 0.5]
+        #[arg(long)]
+        pass_ratio: Option<f32>,
+        #[command(flatten)]
+        grading: GradingArgs,
+    },
+    /// Combine the filled tables of several tutors into one table for upload
+    Merge {
+        /// Filled tables to combine, rows of the first one come first
+        #[arg(required = true)]
+        tables: Vec<PathBuf>,
+        /// [default: result.csv]
+        #[arg(short, long)]
+        result_path: Option<PathBuf>,
+        /// The `split.csv` of the submissions, to check that every student was graded by their tutor
+        #[arg(long, value_name = "CSV")]
+        manifest: Option<PathBuf>,
+        /// Abort if a row of a table can't be read instead of copying it unchanged
+        #[arg(long, action = clap::ArgAction::SetTrue, overrides_with = "no_strict")]
+        strict: bool,
+        /// Copy unreadable rows even if the config sets `strict`
+        #[arg(long, action = clap::ArgAction::SetTrue, overrides_with = "strict")]
+        no_strict: bool,
+    },
+    /// Distribute the submission folders among tutors and record who got which in `split.csv`
+    Split {
+        /// Directory containing the student submissions [default: .]
+        path: Option<PathBuf>,
+        /// Names of the tutors, e.g. `anna,ben`
+        #[arg(long, value_delimiter = ',')]
+        tutors: Vec<String>,
+        /// round-robin, size or seeded [default: round-robin, seeded if a seed is given]
+        #[arg(long)]
+        strategy: Option<tutors::Strategy>,
+        /// Seed for shuffling the submissions [default: 0]
+        #[arg(long)]
+        seed: Option<u64>,
+        /// Directory for the folders or zips of the tutors [default: PATH/../]
+        #[arg(short, long)]
+        target_dir: Option<PathBuf>,
+        /// Copy the submissions instead of moving them
+        #[arg(long, action = clap::ArgAction::SetTrue, conflicts_with = "zip", overrides_with = "no_copy")]
+        copy: bool,
+        /// Move the submissions even if the config sets `copy`
+        #[arg(long, action = clap::ArgAction::SetTrue, overrides_with = "copy")]
+        no_copy: bool,
+        /// Write a zip per tutor and leave the submissions in place
+        #[arg(long, action = clap::ArgAction::SetTrue, overrides_with = "no_zip")]
+        zip: bool,
+        /// Write folders even if the config sets `zip`
+        #[arg(long, action = clap::ArgAction::SetTrue, overrides_with = "zip")]
+        no_zip: bool,
+    },
+    /// Semester totals over the filled tables of all assignments
+    Gradebook {
+        /// The gradebook file [default: gradebook.json]
+        #[arg(short, long, global = true)]
+        book: Option<PathBuf>,
+        #[command(subcommand)]
+        command: GradebookCommands,
+    },
+    /// Feedback documents for students
+    Feedback {
+        #[command(subcommand)]
+        command: FeedbackCommands,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum FeedbackCommands {
+    /// Write a report with every tutor comment, its code, subtotals and the score into each
+    /// submission folder
+    Render {
+        /// Directory containing the student submissions [default: .]
+        path: Option<PathBuf>,
+        /// File name of the report without extension [default: feedback]
+        #[arg(short, long)]
+        name: Option<String>,
+        /// maximum points for the assignment [default = 25]
+        #[arg(short, long)]
+        max_points: Option<u8>,
+        /// Lines of code shown before and after each comment [default: 2]
+        #[arg(long)]
+        context: Option<usize>,
+        /// Write a PDF in addition to the HTML page
+        #[arg(long, action = clap::ArgAction::SetTrue, overrides_with = "no_pdf")]
+        pdf: bool,
+        /// Write only the HTML page even if the config sets `pdf`
+        #[arg(long, action = clap::ArgAction::SetTrue, overrides_with = "pdf")]
+        no_pdf: bool,
+        #[command(flatten)]
+        grading: GradingArgs,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum GradebookCommands {
+    /// Add a filled table as an assignment, replacing an earlier import of the same name
+    Import {
         table_path: PathBuf,
-        /// Path to the directory containing the student submissions
-        #[arg(short, long, default_value = ".")]
-        dir_path: PathBuf,
-        #[arg(short, long, default_value = "result.csv")]
-        result_path: PathBuf,
+        /// Name of the assignment [default: file name of the table]
+        #[arg(short, long)]
+        name: Option<String>,
     },
-    Stats,
+    /// Print the points of every student per assignment and in total
+    Show,
+    /// Print the students without a grade per assignment
+    Missing,
+    /// Print the final grade of every student
+    Grades {
+        /// Lowest percentage of the total points for a grade, e.g. `1=87.5`; replaces the
+        /// configured thresholds [default: 1=87.5 2=75 3=62.5 4=50 5=0]
+        #[arg(long, value_name = "GRADE=PERCENT", value_parser = parse_threshold)]
+        threshold: Vec<(String, f32)>,
+    },
+}
+
+#[derive(Args, Debug)]
+struct GradingArgs {
+    /// Maximum deduction for a task tagged with `// Tutor[TASK]:`, e.g. `A2.b=3`
+    #[arg(long = "task-max", value_name = "TASK=POINTS", value_parser = parse_task_max)]
+    task_max: Vec<(String, f32)>,
+    /// Allow `// Tutor: +x` bonus points to raise the score above the maximum, up to CEILING
+    #[arg(long)]
+    ceiling: Option<f32>,
+    /// Sum the points awarded with `// Tutor: x/y` comments instead of subtracting deductions
+    #[arg(long, action = clap::ArgAction::SetTrue, overrides_with = "no_additive")]
+    additive: bool,
+    /// Subtract deductions even if the config sets `additive`
+    #[arg(long, action = clap::ArgAction::SetTrue, overrides_with = "additive")]
+    no_additive: bool,
+    /// Languages whose files are searched for tutor comments [default: java]
+    #[arg(short, long = "lang", value_name = "LANGUAGE")]
+    lang: Vec<String>,
+    /// Additional comment syntax for an extension, e.g. `py=#` or `c=/* */`
+    #[arg(long, value_name = "EXT=SYNTAX", value_parser = parse_comment)]
+    comment: Vec<(String, tutors::CommentSyntax)>,
+}
+
+#[derive(Args, Debug)]
+struct LateArgs {
+    /// Report submissions after this date, e.g. `2024-03-13 23:59`
+    #[arg(long)]
+    deadline: Option<String>,
+    /// Minutes after the deadline that still count as on time [default: 0]
+    #[arg(long, value_name = "MINUTES")]
+    grace: Option<i64>,
+    /// Points deducted per started hour or day after the deadline [default: 0]
+    #[arg(long, value_name = "POINTS")]
+    late_penalty: Option<f32>,
+    /// Unit of the late penalty: hour or day [default: day]
+    #[arg(long, value_name = "UNIT")]
+    late_per: Option<tutors::Unit>,
+    /// Highest total late penalty
+    #[arg(long, value_name = "POINTS")]
+    late_cap: Option<f32>,
 }
 
 #[allow(unused)]
 fn main() -> Result<()> {
     let cli = Cli::parse();
 
-    dbglog!(cli.debug, "Command", &cli.command);
+    if cli.debug {
+        println!("{:9}: {:?}", "Command", &cli.command);
+    }
+
+    let config_path = cli
+        .config
+        .clone()
+        .or_else(|| tutors_config::Config::discover(&std::env::current_dir().ok()?));
+    let config = match &config_path {
+        Some(path) => {
+            if cli.debug {
+                println!("{:9}: {:?}", "Config", path);
+            }
+            tutors_config::Config::load(path)?
+        }
+        None => tutors_config::Config::default(),
+    };
+    let ignored = config.ignored_names();
 
     match cli.command {
         Commands::Zip {
             name,
             path,
             target_dir,
-        } => tutorslib::zipit(name, path.as_path(), target_dir.as_ref()),
+            table,
+            keep,
+            no_keep,
+        } => tutors::zipit(
+            name.or(config.zip.name)
+                .unwrap_or_else(|| "feedback".to_string()),
+            &path.or(config.zip.path).unwrap_or_else(|| ".".into()),
+            target_dir.or(config.zip.target_dir).as_ref(),
+            table.or(config.zip.table_path).as_deref(),
+            flag(keep, no_keep).unwrap_or(config.zip.keep),
+            cli.dry_run,
+        ),
         Commands::Unzip {
             path,
             single,
+            no_single,
             flatten,
+            no_flatten,
             target,
-        } => tutorslib::unzip(&path, single, flatten, target.as_ref(), cli.debug),
+            max_depth,
+            max_size,
+            max_ratio,
+            max_entries,
+        } => {
+            let default = tutors::Limits::default();
+            let limits = tutors::Limits {
+                max_depth: max_depth
+                    .or(config.unzip.max_depth)
+                    .unwrap_or(default.max_depth),
+                max_bytes: max_size
+                    .or(config.unzip.max_size)
+                    .map_or(default.max_bytes, |mib| mib * 1024 * 1024),
+                max_ratio: max_ratio
+                    .or(config.unzip.max_ratio)
+                    .unwrap_or(default.max_ratio),
+                max_entries: max_entries
+                    .or(config.unzip.max_entries)
+                    .unwrap_or(default.max_entries),
+            };
+            tutors::unzip(
+                &path,
+                flag(single, no_single).unwrap_or(config.unzip.single),
+                flag(flatten, no_flatten).unwrap_or(config.unzip.flatten),
+                target.or(config.unzip.target).as_ref(),
+                &ignored,
+                &limits,
+                cli.debug,
+                cli.dry_run,
+            )
+        }
         Commands::Count {
             path,
             target_dir,
             max_points,
-        } => tutorslib::count(&path, &target_dir, &max_points, cli.debug),
+            grading,
+        } => tutors::count(
+            &path
+                .or(config.count.path.clone())
+                .unwrap_or_else(|| ".".into()),
+            &target_dir
+                .or(config.count.target_dir.clone())
+                .unwrap_or_else(|| ".".into()),
+            &max_points.or(config.max_points),
+            &grading.grading(&config)?,
+            cli.debug,
+        ),
         Commands::Fill {
             table_path,
             dir_path,
             result_path,
-        } => tutorslib::fill_table(
-            table_path.as_path(),
-            dir_path.as_path(),
-            result_path.as_path(),
+            grading,
+            html,
+            no_html,
+            unmatched,
+            late,
+            orphans,
+            max_points,
+            strict,
+            no_strict,
+            force,
+            no_force,
+        } => tutors::fill_table(
+            &table_path
+                .or(config.fill.table_path.clone())
+                .ok_or_else(|| anyhow::anyhow!("No table path given"))?,
+            &dir_path
+                .or(config.fill.dir_path.clone())
+                .unwrap_or_else(|| ".".into()),
+            &result_path
+                .or(config.fill.result_path.clone())
+                .unwrap_or_else(|| "result.csv".into()),
+            &grading.grading(&config)?,
+            &tutors::FillOptions {
+                feedback: config.feedback.as_deref(),
+                html: flag(html, no_html).unwrap_or(config.fill.html),
+                unmatched: unmatched.or(config.fill.unmatched).unwrap_or_default(),
+                late: late.policy(&config)?,
+                force: flag(force, no_force).unwrap_or(config.fill.force),
+                orphans: orphans.or(config.fill.orphans.clone()).as_deref(),
+                max_points: max_points.or(config.max_points).map(f32::from),
+                strict: flag(strict, no_strict).unwrap_or(config.fill.strict),
+            },
+            cli.debug,
+        ),
+        Commands::Stats {
+            path,
+            max_points,
+            pass_ratio,
+            grading,
+        } => tutors::stats(
+            &path
+                .or(config.stats.path.clone())
+                .unwrap_or_else(|| ".".into()),
+            &max_points.or(config.max_points),
+            pass_ratio.or(config.stats.pass_ratio).unwrap_or(0.5),
+            &grading.grading(&config)?,
+            cli.debug,
+        ),
+        Commands::Merge {
+            tables,
+            result_path,
+            manifest,
+            strict,
+            no_strict,
+        } => tutors::merge_tables(
+            &tables,
+            &result_path
+                .or(config.merge.result_path.clone())
+                .unwrap_or_else(|| "result.csv".into()),
+            manifest.or(config.merge.manifest.clone()).as_deref(),
+            flag(strict, no_strict).unwrap_or(config.merge.strict),
+            cli.debug,
+        ),
+        Commands::Split {
+            path,
+            tutors,
+            strategy,
+            seed,
+            target_dir,
+            copy,
+            no_copy,
+            zip,
+            no_zip,
+        } => {
+            let seed = seed.or(config.split.seed);
+            let default = match seed {
+                Some(_) => tutors::Strategy::Seeded,
+                None => tutors::Strategy::RoundRobin,
+            };
+            tutors::split(
+                &path
+                    .or(config.split.path.clone())
+                    .unwrap_or_else(|| ".".into()),
+                match tutors.is_empty() {
+                    true => &config.split.tutors,
+                    false => &tutors,
+                },
+                target_dir.or(config.split.target_dir.clone()).as_ref(),
+                &tutors::SplitOptions {
+                    strategy: strategy.or(config.split.strategy).unwrap_or(default),
+                    seed: seed.unwrap_or_default(),
+                    copy: flag(copy, no_copy).unwrap_or(config.split.copy),
+                    zip: flag(zip, no_zip).unwrap_or(config.split.zip),
+                },
+                cli.debug,
+                cli.dry_run,
+            )
+        }
+        Commands::Gradebook { book, command } => {
+            let book = book
+                .or(config.gradebook.path.clone())
+                .unwrap_or_else(|| "gradebook.json".into());
+            match command {
+                GradebookCommands::Import { table_path, name } => {
+                    tutors::gradebook_import(&book, &table_path, name.as_deref(), cli.debug)
+                }
+                GradebookCommands::Show => tutors::gradebook_show(&book),
+                GradebookCommands::Missing => tutors::gradebook_missing(&book),
+                GradebookCommands::Grades { threshold } => {
+                    let thresholds = match (threshold.is_empty(), &config.gradebook.thresholds) {
+                        (false, _) => threshold,
+                        (true, configured) if !configured.is_empty() => {
+                            configured.clone().into_iter().collect()
+                        }
+                        _ => tutors::THRESHOLDS
+                            .iter()
+                            .map(|(grade, min)| (grade.to_string(), *min))
+                            .collect(),
+                    };
+                    tutors::gradebook_grades(&book, &thresholds)
+                }
+            }
+        }
+        Commands::Feedback {
+            command:
+                FeedbackCommands::Render {
+                    path,
+                    name,
+                    max_points,
+                    context,
+                    pdf,
+                    no_pdf,
+                    grading,
+                },
+        } => tutors::render(
+            &path
+                .or(config.render.path.clone())
+                .unwrap_or_else(|| ".".into()),
+            &name
+                .or(config.render.name.clone())
+                .unwrap_or_else(|| "feedback".to_string()),
+            &max_points.or(config.max_points),
+            &grading.grading(&config)?,
+            context.or(config.render.context).unwrap_or(2),
+            flag(pdf, no_pdf).unwrap_or(config.render.pdf),
             cli.debug,
         ),
-        Commands::Stats => tutorslib::stats(),
     }
 }
 
+impl GradingArgs {
+    /// Command line languages replace the configured ones, everything else is added to
+    /// or overrides the config.
+    fn grading(self, config: &tutors_config::Config) -> Result<tutors::Grading> {
+        let names = match self.lang.is_empty() {
+            true => config.languages.clone().unwrap_or_default(),
+            false => self.lang,
+        };
+        let mut languages = match names.is_empty() {
+            true => tutors::Languages::default(),
+            false => tutors::Languages::new(&names)?,
+        };
+        for (ext, syntax) in &config.comments {
+            languages.add(ext, tutors::CommentSyntax::parse(syntax)?)?;
+        }
+        for (ext, syntax) in self.comment {
+            languages.add(&ext, syntax)?;
+        }
+
+        let mut limits = config.task_max.clone();
+        limits.extend(self.task_max);
+
+        Ok(tutors::Grading {
+            languages,
+            limits,
+            ceiling: self.ceiling.or(config.ceiling),
+            mode: match flag(self.additive, self.no_additive).unwrap_or(config.additive) {
+                true => tutors::Mode::Additive,
+                false => tutors::Mode::Deductive,
+            },
+        })
+    }
+}
+
+impl LateArgs {
+    /// The late policy if a deadline is given on the command line or in the config.
+    fn policy(self, config: &tutors_config::Config) -> Result<Option<tutors::LatePolicy>> {
+        let Some(deadline) = self.deadline.or(config.late.deadline.clone()) else {
+            return Ok(None);
+        };
+        let deadline = tutors::parse_date(&deadline)
+            .ok_or_else(|| anyhow::anyhow!("Invalid deadline `{}`", deadline))?;
+
+        Ok(Some(tutors::LatePolicy {
+            deadline,
+            grace: self.grace.or(config.late.grace).unwrap_or(0),
+            penalty: self.late_penalty.or(config.late.penalty).unwrap_or(0.),
+            per: self.late_per.or(config.late.per).unwrap_or_default(),
+            cap: self.late_cap.or(config.late.cap),
+        }))
+    }
+}
+
+/// The value of a boolean flag given as `--<flag>` or `--no-<flag>`, `None` to use the
+/// config.
+fn flag(set: bool, unset: bool) -> Option<bool> {
+    match (set, unset) {
+        (true, _) => Some(true),
+        (_, true) => Some(false),
+        _ => None,
+    }
+}
+
+fn parse_comment(s: &str) -> Result<(String, tutors::CommentSyntax), String> {
+    let (ext, syntax) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected EXT=SYNTAX, got `{}`", s))?;
+    let syntax = tutors::CommentSyntax::parse(syntax).map_err(|e| e.to_string())?;
+    Ok((ext.trim().to_string(), syntax))
+}
+
+fn parse_task_max(s: &str) -> Result<(String, f32), String> {
+    let (task, points) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected TASK=POINTS, got `{}`", s))?;
+    let points = points
+        .replace(',', ".")
+        .parse()
+        .map_err(|_| format!("invalid points `{}`", points))?;
+    Ok((task.trim().to_string(), points))
+}
+
+fn parse_threshold(s: &str) -> Result<(String, f32), String> {
+    let (grade, percent) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected GRADE=PERCENT, got `{}`", s))?;
+    let percent = percent
+        .trim_end_matches('%')
+        .replace(',', ".")
+        .parse()
+        .map_err(|_| format!("invalid percentage `{}`", percent))?;
+    Ok((grade.trim().to_string(), percent))
+}
+
 #[test]
 fn verify_cli() {
     use clap::CommandFactory;
     Cli::command().debug_assert()
 }
+
+#[test]
+fn verify_flag_override() {
+    let unzip = |args: &[&str]| match Cli::parse_from(args).command {
+        Commands::Unzip {
+            single, no_single, ..
+        } => flag(single, no_single),
+        _ => unreachable!(),
+    };
+    assert_eq!(unzip(&["tutors", "unzip", "a.zip"]), None);
+    assert_eq!(
+        unzip(&["tutors", "unzip", "a.zip", "--no-single"]),
+        Some(false)
+    );
+    assert_eq!(
+        unzip(&["tutors", "unzip", "a.zip", "-s", "--no-single"]),
+        Some(false)
+    );
+    assert_eq!(
+        unzip(&["tutors", "unzip", "a.zip", "--no-single", "-s"]),
+        Some(true)
+    );
+}
@@ -0,0 +1,758 @@
+use std::fs::{create_dir, create_dir_all, remove_dir_all, File};
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::Result;
+use zip::ZipArchive;
+
+use crate::tutors_error::{io, Error};
+
+/// Extensions of files that are inspected for archive magic bytes.
+/// Other files such as `.jar` or `.docx` are zips too, but must not be unpacked.
+const ARCHIVE_EXTENSIONS: [&str; 10] = [
+    "zip", "tar", "gz", "tgz", "bz2", "tbz", "tbz2", "xz", "txz", "7z",
+];
+const RATIO_MIN_BYTES: u64 = 1024 * 1024;
+/// Bytes needed to recognize a tar by the `ustar` magic
+const TAR_HEADER_LEN: usize = 262;
+const S_IFLNK: u32 = 0o120000;
+const S_IFMT: u32 = 0o170000;
+
+/// The compressed kinds usually hold a tar, a single compressed file is extracted as the
+/// file name without the compression extension.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArchiveKind {
+    Zip,
+    Tar,
+    TarGz,
+    TarBz2,
+    TarXz,
+    SevenZ,
+}
+
+/// Limits applied while extracting the archives of a single submission.
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    /// How deep archives may be nested inside a submission archive
+    pub max_depth: usize,
+    /// Uncompressed bytes a single submission may extract in total
+    pub max_bytes: u64,
+    /// Highest accepted ratio of extracted bytes to archive size, checked above 1 MiB
+    pub max_ratio: f64,
+    /// Entries a single submission may extract in total, including directories
+    pub max_entries: u64,
+}
+
+/// What a single submission may still extract, counted down by [`extract`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Budget {
+    pub bytes: u64,
+    pub entries: u64,
+}
+
+/// Files written by [`extract`] and the entries it skipped together with the reason.
+#[derive(Debug, Default)]
+pub struct Extracted {
+    pub files: Vec<PathBuf>,
+    pub rejected: Vec<(String, &'static str)>,
+}
+
+#[derive(Debug, PartialEq)]
+enum EntryKind {
+    File,
+    Dir,
+    Link,
+    Other,
+}
+
+/// State shared by all entries of a single archive.
+struct Extraction<'a> {
+    target: &'a Path,
+    root: PathBuf,
+    limits: &'a Limits,
+    budget: &'a mut Budget,
+    /// Bytes this archive may expand to before the ratio limit is hit
+    ratio_cap: u64,
+    written: u64,
+    extracted: Extracted,
+    debug: bool,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_depth: 3,
+            max_bytes: 100 * 1024 * 1024,
+            max_ratio: 100.,
+            max_entries: 10_000,
+        }
+    }
+}
+
+impl Budget {
+    /// The budget of a single submission.
+    pub fn new(limits: &Limits) -> Self {
+        Budget {
+            bytes: limits.max_bytes,
+            entries: limits.max_entries,
+        }
+    }
+
+    /// No limit on bytes and entries, for the outer archive holding all submissions.
+    pub fn unlimited() -> Self {
+        Budget {
+            bytes: u64::MAX,
+            entries: u64::MAX,
+        }
+    }
+}
+
+impl ArchiveKind {
+    /// Detects the archive type of `path` by its magic bytes.
+    ///
+    /// Only files with an archive extension are inspected, the extension itself doesn't
+    /// matter otherwise, so a 7z named `.zip` is still recognized.
+    pub fn detect(path: &Path) -> Option<ArchiveKind> {
+        if !has_archive_extension(path) || !path.is_file() {
+            return None;
+        }
+
+        let mut header = [0u8; TAR_HEADER_LEN];
+        let mut file = File::open(path).ok()?;
+        let mut len = 0;
+        while len < header.len() {
+            match file.read(&mut header[len..]) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => len += n,
+            }
+        }
+        ArchiveKind::from_magic(&header[..len])
+    }
+
+    fn from_magic(header: &[u8]) -> Option<ArchiveKind> {
+        match header {
+            [b'P', b'K', 3, 4, ..] | [b'P', b'K', 5, 6, ..] => Some(ArchiveKind::Zip),
+            [0x1f, 0x8b, ..] => Some(ArchiveKind::TarGz),
+            [b'B', b'Z', b'h', ..] => Some(ArchiveKind::TarBz2),
+            [0xfd, b'7', b'z', b'X', b'Z', 0, ..] => Some(ArchiveKind::TarXz),
+            [b'7', b'z', 0xbc, 0xaf, 0x27, 0x1c, ..] => Some(ArchiveKind::SevenZ),
+            _ if header.len() >= TAR_HEADER_LEN && &header[257..262] == b"ustar" => {
+                Some(ArchiveKind::Tar)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Whether [`ArchiveKind::detect`] looks at the content of `path` at all.
+pub fn has_archive_extension(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ARCHIVE_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+/// The files [`extract`] would write for the archive at `path`, relative to the target
+/// directory. Nothing is extracted, entries that would be skipped are left out.
+pub fn list(path: &Path, kind: ArchiveKind) -> Result<Vec<PathBuf>> {
+    let open = || File::open(path).map_err(io(path));
+    let names = match kind {
+        ArchiveKind::Zip => list_zip(path),
+        ArchiveKind::Tar => list_tar(open()?),
+        ArchiveKind::TarGz => list_compressed(path, flate2::read::GzDecoder::new(open()?)),
+        ArchiveKind::TarBz2 => list_compressed(path, bzip2::read::BzDecoder::new(open()?)),
+        ArchiveKind::TarXz => list_compressed(path, xz2::read::XzDecoder::new(open()?)),
+        ArchiveKind::SevenZ => list_7z(path),
+    }
+    .map_err(|err| Error::BadArchive {
+        path: path.to_path_buf(),
+        reason: format!("{:#}", err),
+    })?;
+
+    Ok(names
+        .iter()
+        .filter_map(|name| enclosed(name))
+        .filter(|relative| !relative.as_os_str().is_empty())
+        .collect())
+}
+
+fn list_zip(path: &Path) -> Result<Vec<String>> {
+    let mut archive = ZipArchive::new(File::open(path)?)?;
+    let mut names = Vec::new();
+    for i in 0..archive.len() {
+        let file = archive.by_index_raw(i)?;
+        let link = file
+            .unix_mode()
+            .is_some_and(|mode| mode & S_IFMT == S_IFLNK);
+        if !link && !file.is_dir() {
+            names.push(file.name().to_string());
+        }
+    }
+    Ok(names)
+}
+
+fn list_compressed(path: &Path, mut reader: impl Read) -> Result<Vec<String>> {
+    let mut header = Vec::with_capacity(TAR_HEADER_LEN);
+    (&mut reader)
+        .take(TAR_HEADER_LEN as u64)
+        .read_to_end(&mut header)?;
+
+    if ArchiveKind::from_magic(&header) == Some(ArchiveKind::Tar) {
+        return list_tar(std::io::Cursor::new(&header).chain(reader));
+    }
+    let name = path.file_stem().unwrap_or_default().to_string_lossy();
+    Ok(vec![name.to_string()])
+}
+
+fn list_tar(reader: impl Read) -> Result<Vec<String>> {
+    let mut archive = tar::Archive::new(reader);
+    let mut names = Vec::new();
+    for entry in archive.entries()? {
+        let entry = entry?;
+        if matches!(
+            entry.header().entry_type(),
+            tar::EntryType::Regular | tar::EntryType::Continuous
+        ) {
+            names.push(String::from_utf8_lossy(&entry.path_bytes()).to_string());
+        }
+    }
+    Ok(names)
+}
+
+fn list_7z(path: &Path) -> Result<Vec<String>> {
+    let archive = sevenz_rust::SevenZReader::open(path, sevenz_rust::Password::empty())?;
+    Ok(archive
+        .archive()
+        .files
+        .iter()
+        .filter(|entry| !entry.is_directory() && !entry.is_anti_item())
+        .map(|entry| entry.name().to_string())
+        .collect())
+}
+
+/// Extracts the archive at `path` into `target`.
+///
+/// Entries that would end up outside of `target` are skipped: paths containing `..`,
+/// absolute paths, links and paths through existing links. Fails with
+/// [`Error::LimitExceeded`] once the `budget` is used up or the archive expands too much,
+/// and with [`Error::BadArchive`] if it can't be read.
+///
+/// The archive is extracted into a hidden directory in `target` first and only moved into
+/// place if it was extracted completely, so a failed archive leaves nothing behind.
+pub fn extract(
+    path: &Path,
+    kind: ArchiveKind,
+    target: &Path,
+    limits: &Limits,
+    budget: &mut Budget,
+    debug: bool,
+) -> Result<Extracted> {
+    create_dir_all(target).map_err(io(target))?;
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let staging = target.join(format!(".{}.extracting", file_name));
+    if staging.exists() {
+        remove_dir_all(&staging).map_err(io(&staging))?;
+    }
+    create_dir(&staging).map_err(io(&staging))?;
+
+    let result = extract_staged(path, kind, &staging, limits, budget, debug)
+        .and_then(|extracted| settle(&staging, target, extracted));
+    remove_dir_all(&staging).map_err(io(&staging))?;
+    result
+}
+
+fn extract_staged(
+    path: &Path,
+    kind: ArchiveKind,
+    target: &Path,
+    limits: &Limits,
+    budget: &mut Budget,
+    debug: bool,
+) -> Result<Extracted> {
+    let archive_len = std::fs::metadata(path).map_err(io(path))?.len();
+    let mut extraction = Extraction {
+        target,
+        root: target.canonicalize().map_err(io(target))?,
+        limits,
+        budget,
+        ratio_cap: ((archive_len as f64 * limits.max_ratio) as u64).max(RATIO_MIN_BYTES),
+        written: 0,
+        extracted: Extracted::default(),
+        debug,
+    };
+
+    let result = match kind {
+        ArchiveKind::Zip => extract_zip(path, &mut extraction),
+        ArchiveKind::Tar => extract_tar(File::open(path).map_err(io(path))?, &mut extraction),
+        ArchiveKind::TarGz => extract_compressed(
+            path,
+            flate2::read::GzDecoder::new(File::open(path).map_err(io(path))?),
+            &mut extraction,
+        ),
+        ArchiveKind::TarBz2 => extract_compressed(
+            path,
+            bzip2::read::BzDecoder::new(File::open(path).map_err(io(path))?),
+            &mut extraction,
+        ),
+        ArchiveKind::TarXz => extract_compressed(
+            path,
+            xz2::read::XzDecoder::new(File::open(path).map_err(io(path))?),
+            &mut extraction,
+        ),
+        ArchiveKind::SevenZ => extract_7z(path, &mut extraction),
+    };
+
+    // Limits are reported as they are, everything else means the archive is broken
+    result.map_err(|err| match err.downcast::<Error>() {
+        Ok(err) => err,
+        Err(err) => Error::BadArchive {
+            path: path.to_path_buf(),
+            reason: format!("{:#}", err),
+        },
+    })?;
+
+    Ok(extraction.extracted)
+}
+
+/// Moves everything extracted into `staging` to `target`, merging directories that already
+/// exist. Directories that are links in `target` are skipped like links in the archive.
+fn settle(staging: &Path, target: &Path, mut extracted: Extracted) -> Result<Extracted> {
+    let mut skipped = Vec::new();
+    move_entries(staging, target, staging, &mut skipped)?;
+
+    extracted.files = extracted
+        .files
+        .into_iter()
+        .filter_map(|file| {
+            let relative = file.strip_prefix(staging).ok()?;
+            match skipped.iter().any(|dir| relative.starts_with(dir)) {
+                true => None,
+                false => Some(target.join(relative)),
+            }
+        })
+        .collect();
+    for dir in skipped {
+        extracted.rejected.push((
+            dir.to_string_lossy().to_string(),
+            "path leads through a link outside the target directory",
+        ));
+    }
+    Ok(extracted)
+}
+
+fn move_entries(from: &Path, to: &Path, staging: &Path, skipped: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in std::fs::read_dir(from).map_err(io(from))? {
+        let entry = entry.map_err(io(from))?;
+        let source = entry.path();
+        let dest = to.join(entry.file_name());
+        let existing = std::fs::symlink_metadata(&dest).ok();
+        match existing {
+            Some(meta) if source.is_dir() && meta.is_dir() => {
+                move_entries(&source, &dest, staging, skipped)?;
+            }
+            Some(meta) if source.is_dir() && meta.file_type().is_symlink() => {
+                let relative = source.strip_prefix(staging).unwrap_or(&source);
+                skipped.push(relative.to_path_buf());
+            }
+            _ => std::fs::rename(&source, &dest).map_err(io(&dest))?,
+        }
+    }
+    Ok(())
+}
+
+fn extract_zip(path: &Path, extraction: &mut Extraction) -> Result<()> {
+    let mut archive = ZipArchive::new(File::open(path)?)?;
+
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        let name = file.name().to_string();
+        let mode = file.unix_mode();
+        let kind = match mode {
+            Some(mode) if mode & S_IFMT == S_IFLNK => EntryKind::Link,
+            _ if file.is_dir() => EntryKind::Dir,
+            _ => EntryKind::File,
+        };
+        let size = file.size();
+        extraction.entry(&name, kind, size, mode, &mut file)?;
+    }
+
+    Ok(())
+}
+
+/// Extracts a compressed tar, or the decompressed file named after the file stem of `path`
+/// if it isn't a tar, e.g. `Main.java` out of `Main.java.gz`.
+fn extract_compressed(
+    path: &Path,
+    mut reader: impl Read,
+    extraction: &mut Extraction,
+) -> Result<()> {
+    let mut header = Vec::with_capacity(TAR_HEADER_LEN);
+    (&mut reader)
+        .take(TAR_HEADER_LEN as u64)
+        .read_to_end(&mut header)?;
+    let mut stream = std::io::Cursor::new(&header).chain(reader);
+
+    if ArchiveKind::from_magic(&header) == Some(ArchiveKind::Tar) {
+        return extract_tar(stream, extraction);
+    }
+    let name = path.file_stem().unwrap_or_default().to_string_lossy();
+    extraction.entry(&name, EntryKind::File, 0, None, &mut stream)
+}
+
+fn extract_tar(reader: impl Read, extraction: &mut Extraction) -> Result<()> {
+    let mut archive = tar::Archive::new(reader);
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let name = String::from_utf8_lossy(&entry.path_bytes()).to_string();
+        let kind = match entry.header().entry_type() {
+            tar::EntryType::Regular | tar::EntryType::Continuous => EntryKind::File,
+            tar::EntryType::Directory => EntryKind::Dir,
+            tar::EntryType::Symlink | tar::EntryType::Link => EntryKind::Link,
+            tar::EntryType::XGlobalHeader | tar::EntryType::XHeader => continue,
+            _ => EntryKind::Other,
+        };
+        let size = entry.header().size().unwrap_or(0);
+        let mode = entry.header().mode().ok();
+        extraction.entry(&name, kind, size, mode, &mut entry)?;
+    }
+
+    Ok(())
+}
+
+fn extract_7z(path: &Path, extraction: &mut Extraction) -> Result<()> {
+    let mut archive = sevenz_rust::SevenZReader::open(path, sevenz_rust::Password::empty())?;
+    let mut failure = None;
+
+    archive.for_each_entries(|entry, data| {
+        if entry.is_anti_item() {
+            return Ok(true);
+        }
+        let kind = match entry.is_directory() {
+            true => EntryKind::Dir,
+            false => EntryKind::File,
+        };
+        if let Err(err) = extraction.entry(entry.name(), kind, entry.size(), None, data) {
+            failure = Some(err);
+            return Ok(false);
+        }
+        // Entries of a solid block share one stream, skipped data has to be consumed
+        std::io::copy(data, &mut std::io::sink())?;
+        Ok(true)
+    })?;
+
+    match failure {
+        Some(err) => Err(err),
+        None => Ok(()),
+    }
+}
+
+impl Extraction<'_> {
+    fn entry(
+        &mut self,
+        name: &str,
+        kind: EntryKind,
+        size: u64,
+        mode: Option<u32>,
+        reader: &mut dyn Read,
+    ) -> Result<()> {
+        if self.budget.entries == 0 {
+            return Err(Error::LimitExceeded(format!(
+                "more than {} entries",
+                self.limits.max_entries
+            ))
+            .into());
+        }
+        self.budget.entries -= 1;
+        let relative = match enclosed(name) {
+            Some(relative) => relative,
+            None => return self.reject(name, "path leaves the target directory"),
+        };
+        if relative.as_os_str().is_empty() {
+            return Ok(());
+        }
+        match kind {
+            EntryKind::Link => return self.reject(name, "link"),
+            EntryKind::Other => return self.reject(name, "unsupported entry type"),
+            EntryKind::File | EntryKind::Dir => {}
+        }
+
+        let outpath = self.target.join(relative);
+        let dir = match kind {
+            EntryKind::Dir => outpath.as_path(),
+            _ => outpath.parent().unwrap_or(self.target),
+        };
+        create_dir_all(dir).map_err(io(dir))?;
+        if !dir.canonicalize().map_err(io(dir))?.starts_with(&self.root) {
+            return self.reject(
+                name,
+                "path leads through a link outside the target directory",
+            );
+        }
+
+        dbglog!(self.debug, "Extract", outpath);
+
+        if kind == EntryKind::File {
+            if size > self.budget.bytes {
+                return Err(self.size_exceeded());
+            }
+            let ratio_left = self.ratio_cap.saturating_sub(self.written);
+            let cap = self.budget.bytes.min(ratio_left);
+
+            let mut outfile = File::create(&outpath).map_err(io(&outpath))?;
+            let written = std::io::copy(&mut reader.take(cap.saturating_add(1)), &mut outfile)?;
+            if written > cap {
+                std::fs::remove_file(&outpath).map_err(io(&outpath))?;
+                return Err(match self.budget.bytes <= ratio_left {
+                    true => self.size_exceeded(),
+                    false => Error::LimitExceeded(format!(
+                        "archive expands to more than {} times its size",
+                        self.limits.max_ratio
+                    ))
+                    .into(),
+                });
+            }
+            self.budget.bytes -= written;
+            self.written += written;
+            self.extracted.files.push(outpath.clone());
+        }
+
+        #[cfg(unix)]
+        if let Some(mode) = mode {
+            use std::os::unix::fs::PermissionsExt;
+            let owner = if kind == EntryKind::Dir { 0o700 } else { 0o600 };
+            let permissions = std::fs::Permissions::from_mode(mode & 0o777 | owner);
+            std::fs::set_permissions(&outpath, permissions).map_err(io(&outpath))?;
+        }
+
+        Ok(())
+    }
+
+    fn reject(&mut self, name: &str, reason: &'static str) -> Result<()> {
+        self.extracted.rejected.push((name.to_string(), reason));
+        Ok(())
+    }
+
+    fn size_exceeded(&self) -> anyhow::Error {
+        Error::LimitExceeded(format!(
+            "more than {} bytes uncompressed",
+            self.limits.max_bytes
+        ))
+        .into()
+    }
+}
+
+/// The relative path of an archive entry, `None` if it would leave the target directory.
+fn enclosed(name: &str) -> Option<PathBuf> {
+    if name.contains('\0') {
+        return None;
+    }
+    let name = name.replace('\\', "/");
+    let mut path = PathBuf::new();
+    for component in Path::new(&name).components() {
+        match component {
+            Component::Normal(part) => path.push(part),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_enclosed() {
+        assert_eq!(enclosed("a/./b.java"), Some(PathBuf::from("a/b.java")));
+        assert_eq!(enclosed("a\\b.java"), Some(PathBuf::from("a/b.java")));
+        assert_eq!(enclosed("../evil"), None);
+        assert_eq!(enclosed("a/../../evil"), None);
+        assert_eq!(enclosed("/etc/passwd"), None);
+    }
+
+    #[test]
+    fn test_from_magic() {
+        assert_eq!(
+            ArchiveKind::from_magic(b"PK\x03\x04rest"),
+            Some(ArchiveKind::Zip)
+        );
+        assert_eq!(
+            ArchiveKind::from_magic(&[0x1f, 0x8b, 8]),
+            Some(ArchiveKind::TarGz)
+        );
+        assert_eq!(
+            ArchiveKind::from_magic(b"BZh91AY"),
+            Some(ArchiveKind::TarBz2)
+        );
+        assert_eq!(
+            ArchiveKind::from_magic(&[b'7', b'z', 0xbc, 0xaf, 0x27, 0x1c, 0, 4]),
+            Some(ArchiveKind::SevenZ)
+        );
+
+        let mut tar = [0u8; 512];
+        tar[257..262].copy_from_slice(b"ustar");
+        assert_eq!(ArchiveKind::from_magic(&tar), Some(ArchiveKind::Tar));
+        assert_eq!(ArchiveKind::from_magic(b"class Main {}"), None);
+    }
+
+    #[test]
+    fn test_extract_tar_gz() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let dir = tmp.path().to_path_buf();
+
+        let archive_path = dir.join("submission.tgz");
+        let encoder = flate2::write::GzEncoder::new(
+            File::create(&archive_path)?,
+            flate2::Compression::default(),
+        );
+        let mut builder = tar::Builder::new(encoder);
+        let mut header = tar::Header::new_gnu();
+        header.set_size(13);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append_data(&mut header, "src/Main.java", &b"class Main {}"[..])?;
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Symlink);
+        header.set_size(0);
+        header.set_cksum();
+        builder.append_link(&mut header, "link", "/etc/passwd")?;
+        builder.into_inner()?.finish()?;
+
+        let kind = ArchiveKind::detect(&archive_path).unwrap();
+        assert_eq!(kind, ArchiveKind::TarGz);
+
+        let target = dir.join("out");
+        let mut budget = Budget::unlimited();
+        let extracted = extract(
+            &archive_path,
+            kind,
+            &target,
+            &Limits::default(),
+            &mut budget,
+            false,
+        )?;
+
+        assert_eq!(extracted.files, [target.join("src/Main.java")]);
+        assert_eq!(extracted.rejected, [("link".to_string(), "link")]);
+        assert!(!target.join("link").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_compressed_file() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let dir = tmp.path().to_path_buf();
+        let archive_path = dir.join("Main.java.gz");
+        let mut encoder = flate2::write::GzEncoder::new(
+            File::create(&archive_path)?,
+            flate2::Compression::default(),
+        );
+        std::io::Write::write_all(&mut encoder, b"class Main {}")?;
+        encoder.finish()?;
+
+        let kind = ArchiveKind::detect(&archive_path).unwrap();
+        let target = dir.join("out");
+        let mut budget = Budget::unlimited();
+        let extracted = extract(
+            &archive_path,
+            kind,
+            &target,
+            &Limits::default(),
+            &mut budget,
+            false,
+        )?;
+
+        assert_eq!(extracted.files, [target.join("Main.java")]);
+        assert_eq!(
+            std::fs::read_to_string(target.join("Main.java"))?,
+            "class Main {}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_7z() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let dir = tmp.path().to_path_buf();
+        create_dir_all(dir.join("src/x"))?;
+        std::fs::write(dir.join("src/x/Main.java"), "class Main {}")?;
+        std::fs::write(dir.join("src/x/Util.java"), "class Util {}")?;
+
+        // Misnamed archives are detected by their content
+        let archive_path = dir.join("submission.zip");
+        sevenz_rust::compress_to_path(dir.join("src"), &archive_path)?;
+
+        let kind = ArchiveKind::detect(&archive_path).unwrap();
+        assert_eq!(kind, ArchiveKind::SevenZ);
+
+        let target = dir.join("out");
+        let mut budget = Budget::unlimited();
+        let extracted = extract(
+            &archive_path,
+            kind,
+            &target,
+            &Limits::default(),
+            &mut budget,
+            false,
+        )?;
+
+        assert_eq!(extracted.files.len(), 2);
+        assert_eq!(
+            std::fs::read_to_string(target.join("x/Util.java"))?,
+            "class Util {}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_limits() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let dir = tmp.path().to_path_buf();
+        let archive_path = dir.join("submission.zip");
+        let mut zip = zip::ZipWriter::new(File::create(&archive_path)?);
+        for i in 0..5 {
+            zip.start_file(format!("src/File{}.java", i), Default::default())?;
+            std::io::Write::write_all(&mut zip, &[b'x'; 100])?;
+        }
+        zip.finish()?;
+
+        let target = dir.join("out");
+        create_dir_all(&target)?;
+        std::fs::write(target.join("Main.java"), "class Main {}")?;
+        let limits = Limits {
+            max_entries: 3,
+            ..Limits::default()
+        };
+        let extract = |limits: &Limits| {
+            let mut budget = Budget::new(limits);
+            extract(
+                &archive_path,
+                ArchiveKind::Zip,
+                &target,
+                limits,
+                &mut budget,
+                false,
+            )
+        };
+
+        let err = extract(&limits).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::LimitExceeded(_))
+        ));
+        let limits = Limits {
+            max_bytes: 250,
+            ..Limits::default()
+        };
+        assert!(extract(&limits).is_err());
+        let left: Vec<_> = std::fs::read_dir(&target)?.flatten().collect();
+        assert_eq!(left.len(), 1);
+
+        let extracted = extract(&Limits::default())?;
+        assert_eq!(extracted.files.len(), 5);
+        assert!(target.join("src/File4.java").is_file());
+        assert!(target.join("Main.java").is_file());
+        Ok(())
+    }
+}
@@ -0,0 +1,253 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use serde::Deserialize;
+
+use tutors::{Strategy, Unit, Unmatched};
+
+pub const CONFIG_FILE: &str = "tutors.toml";
+/// Files and directories removed after unzipping unless `ignored_names` is configured
+const IGNORED_NAMES: [&str; 6] = ["__macosx", ".git", ".idea", ".ds_store", ".iml", ".class"];
+
+/// Per-course or per-assignment defaults, read from `tutors.toml`.
+///
+/// Every value is optional; arguments given on the command line take precedence.
+/// Relative paths are resolved against the directory containing the config file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub max_points: Option<u8>,
+    /// Text written at the top of every feedback comment instead of the table language's default
+    pub feedback: Option<String>,
+    /// Files and directories removed after unzipping (case-insensitive substring match)
+    pub ignored_names: Option<Vec<String>>,
+    pub languages: Option<Vec<String>>,
+    /// Additional comment syntax per extension, e.g. `py = "#"`
+    pub comments: HashMap<String, String>,
+    pub task_max: HashMap<String, f32>,
+    /// Highest reachable score with bonus points, see `--ceiling`
+    pub ceiling: Option<f32>,
+    /// Grade by awarding points, see `--additive`
+    pub additive: bool,
+    pub zip: ZipConfig,
+    pub unzip: UnzipConfig,
+    pub count: CountConfig,
+    pub fill: FillConfig,
+    /// Deadline and late penalty of the assignment, used by `fill`
+    pub late: LateConfig,
+    pub stats: StatsConfig,
+    pub render: RenderConfig,
+    pub merge: MergeConfig,
+    pub split: SplitConfig,
+    pub gradebook: GradebookConfig,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ZipConfig {
+    pub name: Option<String>,
+    pub path: Option<PathBuf>,
+    pub target_dir: Option<PathBuf>,
+    pub table_path: Option<PathBuf>,
+    pub keep: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UnzipConfig {
+    pub single: bool,
+    pub flatten: bool,
+    pub target: Option<PathBuf>,
+    pub max_depth: Option<usize>,
+    /// Maximum uncompressed size of a single submission in MiB
+    pub max_size: Option<u64>,
+    pub max_ratio: Option<f64>,
+    pub max_entries: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CountConfig {
+    pub path: Option<PathBuf>,
+    pub target_dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FillConfig {
+    pub table_path: Option<PathBuf>,
+    pub dir_path: Option<PathBuf>,
+    pub result_path: Option<PathBuf>,
+    pub html: bool,
+    pub unmatched: Option<Unmatched>,
+    pub orphans: Option<PathBuf>,
+    pub strict: bool,
+    pub force: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LateConfig {
+    /// e.g. `2024-03-13 23:59` or `Mittwoch, 13. März 2024, 23:59`
+    pub deadline: Option<String>,
+    /// Minutes after the deadline that still count as on time
+    pub grace: Option<i64>,
+    pub penalty: Option<f32>,
+    pub per: Option<Unit>,
+    pub cap: Option<f32>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StatsConfig {
+    pub path: Option<PathBuf>,
+    pub pass_ratio: Option<f32>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RenderConfig {
+    pub path: Option<PathBuf>,
+    pub name: Option<String>,
+    pub context: Option<usize>,
+    pub pdf: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MergeConfig {
+    pub result_path: Option<PathBuf>,
+    /// `split.csv` to check the merged tables against
+    pub manifest: Option<PathBuf>,
+    pub strict: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SplitConfig {
+    pub path: Option<PathBuf>,
+    pub tutors: Vec<String>,
+    pub strategy: Option<Strategy>,
+    pub seed: Option<u64>,
+    pub target_dir: Option<PathBuf>,
+    pub copy: bool,
+    pub zip: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GradebookConfig {
+    pub path: Option<PathBuf>,
+    /// Lowest percentage of the total points per grade, e.g. `"1" = 87.5`
+    pub thresholds: HashMap<String, f32>,
+}
+
+impl Config {
+    /// Looks for `tutors.toml` in `start` and all of its ancestors.
+    pub fn discover(start: &Path) -> Option<PathBuf> {
+        start
+            .ancestors()
+            .map(|dir| dir.join(CONFIG_FILE))
+            .find(|path| path.is_file())
+    }
+
+    pub fn load(path: &Path) -> Result<Config> {
+        let content = std::fs::read_to_string(path)?;
+        let mut config: Config = toml::from_str(&content)
+            .map_err(|e| anyhow::anyhow!("Invalid config {}: {}", path.display(), e))?;
+
+        // File names are compared in lower case
+        for name in config.ignored_names.iter_mut().flatten() {
+            *name = name.to_lowercase();
+        }
+
+        let base = path.parent().unwrap_or(Path::new("."));
+        for path in [
+            &mut config.zip.path,
+            &mut config.zip.target_dir,
+            &mut config.zip.table_path,
+            &mut config.unzip.target,
+            &mut config.count.path,
+            &mut config.count.target_dir,
+            &mut config.fill.table_path,
+            &mut config.fill.dir_path,
+            &mut config.fill.result_path,
+            &mut config.fill.orphans,
+            &mut config.stats.path,
+            &mut config.render.path,
+            &mut config.merge.result_path,
+            &mut config.merge.manifest,
+            &mut config.split.path,
+            &mut config.split.target_dir,
+            &mut config.gradebook.path,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            if path.is_relative() {
+                *path = base.join(&*path);
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// The configured `ignored_names`, or the built-in ones.
+    pub fn ignored_names(&self) -> Vec<String> {
+        match &self.ignored_names {
+            Some(names) => names.clone(),
+            None => IGNORED_NAMES.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+max_points = 24
+languages = ["java", "python"]
+ignored_names = ["__MACOSX", "Thumbs.db"]
+
+[comments]
+erl = "%"
+
+[task_max]
+"A2.b" = 3
+
+[fill]
+dir_path = "submissions"
+html = true
+"#;
+
+    #[test]
+    fn test_load() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().to_path_buf();
+        let path = dir.join(CONFIG_FILE);
+        std::fs::write(&path, CONFIG).unwrap();
+
+        let config = Config::load(&path).unwrap();
+        assert_eq!(config.max_points, Some(24));
+        assert_eq!(config.comments["erl"], "%");
+        assert_eq!(
+            config.ignored_names,
+            Some(vec!["__macosx".to_string(), "thumbs.db".to_string()])
+        );
+        assert_eq!(config.task_max["A2.b"], 3.);
+        assert_eq!(config.fill.dir_path, Some(dir.join("submissions")));
+        assert!(config.fill.html);
+        assert_eq!(config.zip.name, None);
+
+        let nested = dir.join("nested");
+        std::fs::create_dir_all(&nested).unwrap();
+        assert_eq!(Config::discover(&nested), Some(path));
+    }
+
+    #[test]
+    fn test_unknown_field() {
+        assert!(toml::from_str::<Config>("max_point = 3").is_err());
+    }
+}
@@ -1,21 +1,28 @@
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+use anyhow::Result;
 use regex::Regex;
 use serde::{Deserialize, Deserializer, Serialize};
 
+use crate::tutors_error::{io, Error};
+
+/// A row of the Moodle grading table.
 #[derive(Debug, Deserialize, Serialize, PartialEq, Clone)]
-#[allow(unused)]
 pub struct Record {
     #[serde(rename = "ID")]
     #[serde(deserialize_with = "deserialize_id")]
     #[serde(serialize_with = "serialize_id")]
     pub id: String,
     #[serde(rename = "Vollständiger Name")]
-    name: String,
+    pub name: String,
     #[serde(rename = "ID-Nummer")]
-    id_number: String,
+    pub id_number: String,
     #[serde(rename = "E-Mail-Adresse")]
-    email: String,
+    pub email: String,
     #[serde(rename = "Status")]
-    status: String,
+    pub status: String,
     #[serde(rename = "Bewertung")]
     #[serde(deserialize_with = "deserialize_points")]
     #[serde(serialize_with = "serialize_points")]
@@ -25,30 +32,349 @@ pub struct Record {
     #[serde(serialize_with = "serialize_max_points")]
     pub max_points: f32,
     #[serde(rename = "Bewertung kann geändert werden")]
-    rating_changeable: String,
+    pub rating_changeable: String,
     #[serde(rename = "Zuletzt geändert (Abgabe)")]
-    last_change_submission: String,
+    pub last_change_submission: String,
     #[serde(rename = "Zuletzt geändert (Bewertung)")]
-    last_change_rating: String,
+    pub last_change_rating: String,
     #[serde(rename = "Feedback als Kommentar")]
     pub feedback: String,
 }
 
+/// Values of "Bewertung kann geändert werden" that lock a grade, in all [`LOCALES`].
+const LOCKED: [&str; 3] = ["nein", "no", "non"];
+
+impl Record {
+    /// Whether Moodle doesn't accept a new grade for this row.
+    pub fn is_locked(&self) -> bool {
+        LOCKED.contains(&self.rating_changeable.trim().to_lowercase().as_str())
+    }
+}
+
+/// Column headers and number format of the grading worksheet in one Moodle language.
+#[derive(Debug, PartialEq)]
+pub struct Locale {
+    pub language: &'static str,
+    /// Headers in the order of the [`Record`] fields
+    pub headers: [&'static str; 11],
+    /// Text in front of the participant ID, e.g. `Participant ` in `Participant 1234567`
+    pub id_prefix: &'static str,
+    pub decimal_separator: char,
+    /// Default text at the top of every feedback comment
+    pub feedback: &'static str,
+    /// Feedback of students without a submission, see `fill --unmatched zero`
+    pub no_submission: &'static str,
+    /// Note on a late submission, followed by how late it was and the penalty
+    pub late: &'static str,
+}
+
+/// Known worksheet languages, the first one matches the serde names of [`Record`].
+pub const LOCALES: [Locale; 4] = [
+    Locale {
+        language: "de",
+        headers: [
+            "ID",
+            "Vollständiger Name",
+            "ID-Nummer",
+            "E-Mail-Adresse",
+            "Status",
+            "Bewertung",
+            "Bestwertung",
+            "Bewertung kann geändert werden",
+            "Zuletzt geändert (Abgabe)",
+            "Zuletzt geändert (Bewertung)",
+            "Feedback als Kommentar",
+        ],
+        id_prefix: PREFIX_ID,
+        decimal_separator: ',',
+        feedback: "Bewertung siehe Feedbackdateien.",
+        no_submission: "Keine Abgabe.",
+        late: "Verspätete Abgabe",
+    },
+    Locale {
+        language: "en",
+        headers: [
+            "Identifier",
+            "Full name",
+            "ID number",
+            "Email address",
+            "Status",
+            "Grade",
+            "Maximum Grade",
+            "Grade can be changed",
+            "Last modified (submission)",
+            "Last modified (grade)",
+            "Feedback comments",
+        ],
+        id_prefix: "Participant ",
+        decimal_separator: '.',
+        feedback: "See the feedback files for the grading.",
+        no_submission: "No submission.",
+        late: "Late submission",
+    },
+    Locale {
+        language: "fr",
+        headers: [
+            "Identifiant",
+            "Nom complet",
+            "Numéro d'identification",
+            "Adresse de courriel",
+            "Statut",
+            "Note",
+            "Note maximale",
+            "La note peut être modifiée",
+            "Dernière modification (travail remis)",
+            "Dernière modification (note)",
+            "Feedback par commentaires",
+        ],
+        id_prefix: "Participant ",
+        decimal_separator: ',',
+        feedback: "Voir les fichiers de feedback pour la notation.",
+        no_submission: "Aucun travail remis.",
+        late: "Remise tardive",
+    },
+    Locale {
+        language: "es",
+        headers: [
+            "Identificador",
+            "Nombre completo",
+            "Número de ID",
+            "Dirección de correo",
+            "Estado",
+            "Calificación",
+            "Calificación máxima",
+            "La calificación puede ser cambiada",
+            "Última modificación (entrega)",
+            "Última modificación (calificación)",
+            "Comentarios de retroalimentación",
+        ],
+        id_prefix: "Participante ",
+        decimal_separator: ',',
+        feedback: "Ver los archivos de retroalimentación para la calificación.",
+        no_submission: "Sin entrega.",
+        late: "Entrega tardía",
+    },
+];
+
+/// Columns a worksheet must have: ID, grade and maximum grade
+const REQUIRED_COLUMNS: [usize; 3] = [0, 5, 6];
+
+/// A grading worksheet and the language it was exported in.
+#[derive(Debug)]
+pub struct Table {
+    pub locale: &'static Locale,
+    pub records: Vec<Record>,
+    /// Rows that couldn't be read as a [`Record`]
+    pub rejected: Vec<Rejected>,
+}
+
+/// A row of the worksheet that couldn't be read.
+#[derive(Debug, PartialEq)]
+pub struct Rejected {
+    /// 1-based line number in the file
+    pub line: u64,
+    /// Position among the data rows, where [`write_table`] puts the row back
+    pub row: usize,
+    pub reason: String,
+    /// The row as it was in the file, without the line break
+    pub text: String,
+}
+
 const ID_PATTERN: &str = r"([\d]+)";
 const PREFIX_ID: &str = "Teilnehmer/in";
 
+impl Locale {
+    /// The locale whose headers match the header row best, `None` if the required columns
+    /// are missing in every locale.
+    pub fn detect(headers: &csv::StringRecord) -> Option<&'static Locale> {
+        LOCALES
+            .iter()
+            .enumerate()
+            .filter(|(_, locale)| {
+                REQUIRED_COLUMNS
+                    .iter()
+                    .all(|&i| headers.iter().any(|h| same(h, locale.headers[i])))
+            })
+            // Prefer the first locale if several match equally well
+            .max_by_key(|(i, locale)| {
+                let matching = headers.iter().filter(|h| locale.column(h).is_some());
+                (matching.count(), std::cmp::Reverse(*i))
+            })
+            .map(|(_, locale)| locale)
+    }
+
+    fn column(&self, header: &str) -> Option<usize> {
+        self.headers.iter().position(|name| same(header, name))
+    }
+
+    /// The header row with every known header replaced by its German name.
+    fn canonical(&self, headers: &csv::StringRecord) -> csv::StringRecord {
+        headers
+            .iter()
+            .map(|header| match self.column(header) {
+                Some(i) => LOCALES[0].headers[i],
+                None => header,
+            })
+            .collect()
+    }
+
+    /// `record` as a row of a table in this language.
+    fn row(&self, record: &Record) -> [String; 11] {
+        let number = |points: f32| {
+            format!("{:.2}", points).replace('.', &self.decimal_separator.to_string())
+        };
+        [
+            format!("{}{}", self.id_prefix, record.id),
+            record.name.clone(),
+            record.id_number.clone(),
+            record.email.clone(),
+            record.status.clone(),
+            record.points.map(number).unwrap_or_default(),
+            number(record.max_points),
+            record.rating_changeable.clone(),
+            record.last_change_submission.clone(),
+            record.last_change_rating.clone(),
+            record.feedback.clone(),
+        ]
+    }
+}
+
+/// Compares headers ignoring case, surrounding whitespace and a byte order mark.
+fn same(header: &str, name: &str) -> bool {
+    header.trim_start_matches('\u{feff}').trim().to_lowercase() == name.to_lowercase()
+}
+
+/// Reads a grading table, skipping rows that can't be parsed.
+pub fn read_table(table_path: &Path) -> Result<Table> {
+    let file = File::open(table_path).map_err(io(table_path))?;
+    let table = parse_table(file).map_err(|reason| Error::InvalidTable {
+        path: table_path.to_path_buf(),
+        reason,
+    })?;
+
+    Ok(table)
+}
+
+/// Reads a grading table in any of the [`LOCALES`], detected from the header row.
+pub fn parse_table(mut reader: impl Read) -> Result<Table, String> {
+    // Rejected rows are kept as they are, so the whole table is needed
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data).map_err(|e| e.to_string())?;
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(b',')
+        .has_headers(true)
+        // Rows with missing cells are rejected below instead of failing to read
+        .flexible(true)
+        .from_reader(data.as_slice());
+
+    let headers = reader.headers().map_err(|e| e.to_string())?.clone();
+    let locale = Locale::detect(&headers)
+        .ok_or("unknown header row, expected a Moodle grading worksheet")?;
+    let canonical = locale.canonical(&headers);
+
+    let mut records = Vec::new();
+    let mut rejected = Vec::new();
+    let mut bytes = csv::ByteRecord::new();
+    let mut row = 0;
+    while reader
+        .read_byte_record(&mut bytes)
+        .map_err(|e| e.to_string())?
+    {
+        let record = csv::StringRecord::from_byte_record(bytes.clone())
+            .map_err(|err| err.utf8_error().to_string())
+            .and_then(|cells| {
+                cells
+                    .deserialize::<Record>(Some(&canonical))
+                    .map_err(|err| reason(&err))
+            });
+        match record {
+            Ok(record) => records.push(record),
+            Err(reason) => {
+                // Rows read from a reader always have a position
+                let start = bytes.position().map_or(0, |pos| pos.byte()) as usize;
+                let text = &data[start..reader.position().byte() as usize];
+                rejected.push(Rejected {
+                    line: bytes.position().map_or(0, |pos| pos.line()),
+                    row,
+                    reason,
+                    text: String::from_utf8_lossy(text)
+                        .trim_end_matches(['\r', '\n'])
+                        .to_string(),
+                });
+            }
+        }
+        row += 1;
+    }
+
+    Ok(Table {
+        locale,
+        records,
+        rejected,
+    })
+}
+
+/// The cause of a deserialization error without the position, e.g. ``Invalid rating `x` ``
+fn reason(err: &csv::Error) -> String {
+    match err.kind() {
+        csv::ErrorKind::Deserialize { err, .. } => err.kind().to_string(),
+        _ => err.to_string(),
+    }
+}
+
+/// Writes `records` as a grading table in `locale` that can be uploaded to Moodle, with the
+/// `rejected` rows as they were read at their original position.
+pub fn write_table(
+    table_path: &Path,
+    locale: &Locale,
+    records: &[Record],
+    rejected: &[Rejected],
+) -> Result<()> {
+    let file = File::create(table_path).map_err(io(table_path))?;
+    let mut out = std::io::BufWriter::new(file);
+
+    write_row(&mut out, table_path, &locale.headers)?;
+    let mut rejected = rejected.iter().peekable();
+    let mut rows = 0;
+    for record in records {
+        while let Some(row) = rejected.next_if(|row| row.row <= rows) {
+            writeln!(out, "{}", row.text).map_err(io(table_path))?;
+            rows += 1;
+        }
+        write_row(&mut out, table_path, &locale.row(record))?;
+        rows += 1;
+    }
+    for row in rejected {
+        writeln!(out, "{}", row.text).map_err(io(table_path))?;
+    }
+    out.flush().map_err(io(table_path))?;
+
+    Ok(())
+}
+
+/// Writes `cells` as a single csv row, rejected rows are written as they are in between.
+fn write_row(out: &mut impl Write, path: &Path, cells: &[impl AsRef<str>]) -> Result<(), Error> {
+    let mut wtr = csv::WriterBuilder::new().delimiter(b',').from_writer(out);
+    wtr.write_record(cells.iter().map(AsRef::as_ref))
+        .map_err(|e| invalid(path, e))?;
+    wtr.flush().map_err(io(path))
+}
+
+fn invalid(path: &Path, err: csv::Error) -> Error {
+    Error::InvalidTable {
+        path: path.to_path_buf(),
+        reason: err.to_string(),
+    }
+}
+
 fn deserialize_id<'de, D>(deserializer: D) -> Result<String, D::Error>
 where
     D: Deserializer<'de>,
 {
     let s = String::deserialize(deserializer)?;
-    let re = Regex::new(ID_PATTERN).unwrap();
-    if re.is_match(&s) {
-        let caps = re.captures(&s).unwrap();
-        let id = caps.get(1).unwrap().as_str().to_string();
-        Ok(id)
-    } else {
-        Err(serde::de::Error::custom("Invalid ID"))
+    let re = Regex::new(ID_PATTERN).map_err(serde::de::Error::custom)?;
+    match re.captures(&s).and_then(|caps| caps.get(1)) {
+        Some(id) => Ok(id.as_str().to_string()),
+        None => Err(serde::de::Error::custom(format!("Invalid ID `{}`", s))),
     }
 }
 fn deserialize_max_points<'de, D>(deserializer: D) -> Result<f32, D::Error>
@@ -56,10 +382,12 @@ where
     D: Deserializer<'de>,
 {
     let s = String::deserialize(deserializer)?;
-    let s = s.replace(',', ".");
-    match s.parse() {
+    match s.replace(',', ".").parse() {
         Ok(f) => Ok(f),
-        Err(_) => Err(serde::de::Error::custom("Invalid rating")),
+        Err(_) => Err(serde::de::Error::custom(format!(
+            "Invalid maximum rating `{}`",
+            s
+        ))),
     }
 }
 
@@ -74,7 +402,7 @@ where
         s.replace(',', ".")
             .parse()
             .map(Some)
-            .map_err(serde::de::Error::custom)
+            .map_err(|_| serde::de::Error::custom(format!("Invalid rating `{}`", s)))
     }
 }
 
@@ -103,6 +431,13 @@ where
     }
 }
 
+/// A German grading table with `rows` below the header row.
+#[cfg(test)]
+pub(crate) fn test_table(rows: &str) -> Table {
+    let csv = format!("{}\n{}", LOCALES[0].headers.join(","), rows);
+    parse_table(csv.as_bytes()).unwrap()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -140,6 +475,78 @@ mod tests {
         assert_eq!(DATA, String::from_utf8(wtr.into_inner().unwrap()).unwrap());
     }
 
+    #[test]
+    fn test_locales() {
+        let german = parse_table(DATA.as_bytes()).unwrap();
+        assert_eq!(german.locale.language, "de");
+        assert_eq!(german.records, get_records());
+
+        let english = format!(
+            "\u{feff}{}\n{}\n",
+            LOCALES[1].headers.join(","),
+            "Participant 1234567,asdf ghjklö,12345678,K12345678@students.jku.at,Submitted,13.50,24.00,Yes,,,"
+        );
+        let table = parse_table(english.as_bytes()).unwrap();
+        assert_eq!(table.locale.language, "en");
+        assert_eq!(table.records[0].id, "1234567");
+        assert_eq!(table.records[0].points, Some(13.5));
+        assert_eq!(
+            table.locale.row(&table.records[0])[..7],
+            [
+                "Participant 1234567",
+                "asdf ghjklö",
+                "12345678",
+                "K12345678@students.jku.at",
+                "Submitted",
+                "13.50",
+                "24.00"
+            ]
+        );
+
+        assert!(parse_table("Name,Points\nMax,3\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_write_table() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("table.csv");
+        write_table(&path, &LOCALES[0], &get_records(), &[]).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), DATA);
+    }
+
+    #[test]
+    fn test_rejected() {
+        let data = DATA.replace("Teilnehmer/in7654321", "Teilnehmer/in")
+            + "Teilnehmer/in42,x,1,x@y,s,\"1,x\",\"24,00\",Ja,,,\n";
+        let table = parse_table(data.as_bytes()).unwrap();
+
+        assert_eq!(table.records, get_records()[..1]);
+        assert_eq!(table.rejected.len(), 2);
+        assert_eq!(table.rejected[0].line, 3);
+        assert_eq!(table.rejected[0].reason, "Invalid ID `Teilnehmer/in`");
+        assert_eq!(table.rejected[1].line, 4);
+        assert_eq!(table.rejected[1].reason, "Invalid rating `1,x`");
+
+        assert_eq!(
+            table.rejected[1].text,
+            "Teilnehmer/in42,x,1,x@y,s,\"1,x\",\"24,00\",Ja,,,"
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("table.csv");
+        write_table(&path, table.locale, &table.records, &table.rejected).unwrap();
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(written, data);
+
+        // Rows that aren't valid UTF-8 are kept too
+        let mut data = data.into_bytes();
+        data.extend(b"Teilnehmer/in43,\xff,,,,,\"24,00\",Ja,,,\n");
+        let table = parse_table(data.as_slice()).unwrap();
+        assert_eq!(table.rejected.len(), 3);
+        assert_eq!(table.rejected[2].line, 5);
+        assert!(table.rejected[2].text.starts_with("Teilnehmer/in43,"));
+    }
+
     fn get_records() -> [Record; 2] {
         [
             Record {
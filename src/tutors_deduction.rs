@@ -0,0 +1,324 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use regex::Regex;
+
+use crate::tutors_language::Languages;
+
+/// How tutor comments are found and turned into points.
+#[derive(Debug, Clone, Default)]
+pub struct Grading {
+    pub languages: Languages,
+    pub limits: TaskLimits,
+    /// Highest reachable score when bonus points exceed the deductions.
+    /// Without a ceiling, bonus points only compensate deductions up to the maximum points.
+    pub ceiling: Option<f32>,
+    pub mode: Mode,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Mode {
+    /// Start from the maximum points and subtract deductions
+    #[default]
+    Deductive,
+    /// Start from zero and sum the points awarded with `// Tutor: x/y` comments
+    Additive,
+}
+
+/// Maximum points per task, keyed by the tag used in `// Tutor[<task>]:` comments.
+pub type TaskLimits = HashMap<String, f32>;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Deduction {
+    pub task: Option<String>,
+    /// Absolute value of the points, see `bonus` for the sign
+    pub points: f32,
+    /// `+x` awards bonus points, `-x` and unsigned `x` deduct points
+    pub bonus: bool,
+    /// Set for `x/y` comments, which award `x` of the `y` points of a task
+    pub out_of: Option<f32>,
+    /// Text following the points, empty if the tutor gave no reason
+    pub reason: String,
+    pub file: PathBuf,
+    /// 1-based line number of the comment
+    pub line: usize,
+}
+
+/// Deductions of a single submission, capped per task, its bonus and awarded points.
+#[derive(Debug, Default, PartialEq)]
+pub struct Tally {
+    pub total: f32,
+    pub per_task: BTreeMap<String, f32>,
+    pub bonus: f32,
+    /// Sum of `x` over all `x/y` comments
+    pub awarded: f32,
+    /// Sum of `y` over all `x/y` comments
+    pub available: f32,
+}
+
+/// Collects all tutor comments of `file`, attributing them to `source`.
+///
+/// Fails on a tutor comment without points instead of guessing them.
+///
+/// `patterns` are the comment patterns of the file's language, see [`Languages::patterns`](crate::Languages::patterns).
+pub fn calculate_deduction(
+    source: &Path,
+    file: impl BufRead,
+    patterns: &[Regex],
+) -> Result<Vec<Deduction>> {
+    let mut result = Vec::new();
+
+    for (i, line) in file.lines().map_while(Result::ok).enumerate() {
+        for cap in patterns.iter().flat_map(|re| re.captures_iter(&line)) {
+            let points = match cap.get(3) {
+                Some(points) => points.as_str().parse::<f32>()?,
+                None => {
+                    return Err(anyhow::anyhow!(
+                        "{}:{}: tutor comment without points",
+                        source.display(),
+                        i + 1
+                    ))
+                }
+            };
+            let bonus = cap.get(2).is_some_and(|sign| sign.as_str() == "+");
+            let out_of = cap.get(4).and_then(|out_of| out_of.as_str().parse().ok());
+            let task = cap.get(1).map(|task| task.as_str().trim().to_string());
+            let reason = cap.get(5).map_or("", |r| r.as_str()).trim().to_string();
+            result.push(Deduction {
+                task,
+                points,
+                bonus,
+                out_of,
+                reason,
+                file: source.to_path_buf(),
+                line: i + 1,
+            });
+        }
+    }
+
+    Ok(result)
+}
+
+/// Adds up the `deductions` of a submission per task, capping each task at its limit in
+/// `limits`.
+///
+/// Bonus points and `x/y` comments are summed separately, see [`Tally`].
+pub fn tally(deductions: &[Deduction], limits: &TaskLimits) -> Tally {
+    let mut untagged = 0f32;
+    let mut bonus = 0f32;
+    let mut awarded = 0f32;
+    let mut available = 0f32;
+    let mut per_task = BTreeMap::new();
+
+    for deduction in deductions {
+        if deduction.bonus {
+            bonus += deduction.points;
+            continue;
+        }
+        if let Some(out_of) = deduction.out_of {
+            awarded += deduction.points.min(out_of);
+            available += out_of;
+            continue;
+        }
+        match &deduction.task {
+            Some(task) => *per_task.entry(task.clone()).or_insert(0f32) += deduction.points,
+            None => untagged += deduction.points,
+        }
+    }
+
+    for (task, points) in per_task.iter_mut() {
+        if let Some(limit) = limits.get(task) {
+            *points = points.min(*limit);
+        }
+    }
+
+    Tally {
+        total: untagged + per_task.values().sum::<f32>(),
+        per_task,
+        bonus,
+        awarded,
+        available,
+    }
+}
+
+impl Grading {
+    /// Final score of a submission worth `max_points`, never below zero.
+    ///
+    /// In deductive mode the points missing from `x/y` comments count as deductions,
+    /// in additive mode `-x` comments are subtracted from the awarded points.
+    pub fn score(&self, max_points: f32, tally: &Tally) -> f32 {
+        let ceiling = self.ceiling.map_or(max_points, |c| c.max(max_points));
+        let points = match self.mode {
+            Mode::Deductive => max_points - (tally.available - tally.awarded),
+            Mode::Additive => tally.awarded,
+        };
+        (points - tally.total + tally.bonus).clamp(0., ceiling)
+    }
+
+    /// Describes why the tutor comments don't add up to `max_points` in additive mode.
+    pub fn check(&self, max_points: f32, tally: &Tally) -> Option<String> {
+        match self.mode {
+            Mode::Additive if tally.available > max_points => Some(format!(
+                "tasks are worth {} points, more than the maximum of {}",
+                tally.available, max_points
+            )),
+            Mode::Additive if tally.available < max_points => Some(format!(
+                "only {} of {} points assessed",
+                tally.available, max_points
+            )),
+            _ => None,
+        }
+    }
+}
+
+impl Tally {
+    /// Human readable per-task breakdown, e.g. `A1: -2, A2.b: -1.5, Bonus: +1`
+    pub fn breakdown(&self, separator: &str) -> String {
+        let mut parts: Vec<String> = self
+            .per_task
+            .iter()
+            .map(|(task, points)| format!("{}: -{}", task, points))
+            .collect();
+        if self.available > 0. {
+            parts.push(format!("Awarded: {}/{}", self.awarded, self.available));
+        }
+        if self.bonus > 0. {
+            parts.push(format!("Bonus: +{}", self.bonus));
+        }
+        parts.join(separator)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = r#"
+class Main {
+    // Tutor: -1
+    int a; // Tutor[A1]: -2 wrong type
+    // Tutor[A2.b]: -1.5 missing null check
+    // Tutor[A2.b]: -2
+    // Tutor[ A1 ]: 0.5
+}
+"#;
+
+    #[test]
+    fn test_calculate_deduction() {
+        let languages = Languages::default();
+        let java = languages.patterns("java").unwrap();
+        let deductions =
+            calculate_deduction(Path::new("Main.java"), SOURCE.as_bytes(), java).unwrap();
+
+        assert_eq!(deductions.len(), 5);
+        assert_eq!(
+            deductions[1],
+            Deduction {
+                task: Some("A1".to_string()),
+                points: 2.,
+                bonus: false,
+                out_of: None,
+                reason: "wrong type".to_string(),
+                file: PathBuf::from("Main.java"),
+                line: 4,
+            }
+        );
+        assert_eq!(deductions[0].task, None);
+        assert_eq!(deductions[0].reason, "");
+        assert_eq!(deductions[2].reason, "missing null check");
+        assert_eq!(deductions[4].task, Some("A1".to_string()));
+    }
+
+    #[test]
+    fn test_points() {
+        let languages = Languages::default();
+        let java = languages.patterns("java").unwrap();
+        let source = "// Tutor: -0.25 x\n// Tutor: 12.5\n";
+        let deductions =
+            calculate_deduction(Path::new("Main.java"), source.as_bytes(), java).unwrap();
+
+        assert_eq!(deductions[0].points, 0.25);
+        assert_eq!(deductions[0].reason, "x");
+        assert_eq!(deductions[1].points, 12.5);
+
+        let source = "// Tutor: wrong type\n";
+        assert!(calculate_deduction(Path::new("Main.java"), source.as_bytes(), java).is_err());
+        let source = "// Tutor: .5\n";
+        assert!(calculate_deduction(Path::new("Main.java"), source.as_bytes(), java).is_err());
+    }
+
+    #[test]
+    fn test_tally() {
+        let languages = Languages::default();
+        let java = languages.patterns("java").unwrap();
+        let deductions =
+            calculate_deduction(Path::new("Main.java"), SOURCE.as_bytes(), java).unwrap();
+        let limits = TaskLimits::from([("A2.b".to_string(), 3.)]);
+        let tally = tally(&deductions, &limits);
+
+        assert_eq!(tally.total, 6.5);
+        assert_eq!(tally.per_task["A1"], 2.5);
+        assert_eq!(tally.per_task["A2.b"], 3.);
+        assert_eq!(tally.breakdown(", "), "A1: -2.5, A2.b: -3");
+    }
+
+    #[test]
+    fn test_score() {
+        let source = "// Tutor: -3\n// Tutor: +2 nice\n// Tutor: +4 extra\n";
+        let languages = Languages::default();
+        let java = languages.patterns("java").unwrap();
+        let deductions =
+            calculate_deduction(Path::new("Main.java"), source.as_bytes(), java).unwrap();
+        let tally = tally(&deductions, &TaskLimits::new());
+
+        assert!(deductions[1].bonus);
+        assert_eq!(tally.total, 3.);
+        assert_eq!(tally.bonus, 6.);
+        assert_eq!(tally.breakdown(", "), "Bonus: +6");
+
+        let mut grading = Grading::default();
+        assert_eq!(grading.score(10., &tally), 10.);
+        grading.ceiling = Some(12.);
+        assert_eq!(grading.score(10., &tally), 12.);
+        grading.ceiling = Some(20.);
+        assert_eq!(grading.score(10., &tally), 13.);
+        assert_eq!(
+            grading.score(
+                2.,
+                &Tally {
+                    total: 5.,
+                    ..Tally::default()
+                }
+            ),
+            0.
+        );
+    }
+
+    #[test]
+    fn test_additive() {
+        let source = "// Tutor[A1]: 3/5 partly\n// Tutor[A2]: 6/5\n// Tutor: -1\n";
+        let languages = Languages::default();
+        let java = languages.patterns("java").unwrap();
+        let deductions =
+            calculate_deduction(Path::new("Main.java"), source.as_bytes(), java).unwrap();
+        let tally = tally(&deductions, &TaskLimits::new());
+
+        assert_eq!(deductions[0].out_of, Some(5.));
+        assert_eq!(deductions[0].reason, "partly");
+        assert_eq!(tally.awarded, 8.);
+        assert_eq!(tally.available, 10.);
+        assert_eq!(tally.breakdown(", "), "Awarded: 8/10");
+
+        let mut grading = Grading::default();
+        assert_eq!(grading.score(10., &tally), 7.);
+        assert_eq!(grading.check(10., &tally), None);
+
+        grading.mode = Mode::Additive;
+        assert_eq!(grading.score(12., &tally), 7.);
+        assert_eq!(grading.check(10., &tally), None);
+        assert!(grading.check(12., &tally).is_some());
+        assert!(grading.check(8., &tally).is_some());
+    }
+}
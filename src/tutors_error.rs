@@ -0,0 +1,110 @@
+use std::path::{Path, PathBuf};
+
+/// Errors of the tutors commands, reachable through `anyhow::Error::downcast_ref`.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("{}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("{}: not a valid archive, {reason}", path.display())]
+    BadArchive { path: PathBuf, reason: String },
+    /// A submission exceeded one of the extraction [`crate::tutors_archive::Limits`]
+    #[error("{0}")]
+    LimitExceeded(String),
+    #[error("{}: not a submission folder, expected `<name>_<id>_...`", .0.display())]
+    UnmatchedSubmission(PathBuf),
+    #[error("{}: invalid table, {reason}", path.display())]
+    InvalidTable { path: PathBuf, reason: String },
+    #[error("{}: invalid gradebook, {reason}", path.display())]
+    InvalidGradebook { path: PathBuf, reason: String },
+    /// A student is graded differently in two of the tables given to `merge`
+    #[error("{name} ({id}): different points or feedback in {}", paths(tables))]
+    MergeConflict {
+        id: String,
+        name: String,
+        tables: Vec<PathBuf>,
+    },
+    /// Some submissions failed while the others were processed, see [`Problems`]
+    #[error("{} problem(s):\n{}", .0.len(), list(.0))]
+    Failed(Vec<anyhow::Error>),
+}
+
+/// Problems of single submissions, collected so the remaining submissions are still processed.
+#[derive(Debug, Default)]
+pub struct Problems(Vec<anyhow::Error>);
+
+/// Attaches `path` to an I/O error, use as `.map_err(io(path))`.
+pub fn io(path: &Path) -> impl FnOnce(std::io::Error) -> Error + '_ {
+    move |source| Error::Io {
+        path: path.to_path_buf(),
+        source,
+    }
+}
+
+impl Problems {
+    /// Records the error of `result`, if any, and returns its value otherwise.
+    pub fn check<T, E: Into<anyhow::Error>>(&mut self, result: Result<T, E>) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(err) => {
+                self.0.push(err.into());
+                None
+            }
+        }
+    }
+
+    pub fn push(&mut self, err: impl Into<anyhow::Error>) {
+        self.0.push(err.into());
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Fails with [`Error::Failed`] if any problem was recorded.
+    pub fn finish(self) -> Result<(), Error> {
+        match self.0.is_empty() {
+            true => Ok(()),
+            false => Err(Error::Failed(self.0)),
+        }
+    }
+}
+
+fn paths(paths: &[PathBuf]) -> String {
+    paths
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn list(errors: &[anyhow::Error]) -> String {
+    errors
+        .iter()
+        .map(|err| format!("  {:#}", err))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_problems() {
+        let mut problems = Problems::default();
+        assert_eq!(problems.check(Ok::<_, Error>(1)), Some(1));
+        assert!(problems.is_empty());
+
+        let missing = std::fs::read(Path::new("missing")).map_err(io(Path::new("missing")));
+        assert_eq!(problems.check(missing), None);
+        problems.push(Error::UnmatchedSubmission(PathBuf::from("Max")));
+
+        let err = problems.finish().unwrap_err();
+        let text = err.to_string();
+        assert!(text.starts_with("2 problem(s):\n  missing: "));
+        assert!(text.ends_with("\n  Max: not a submission folder, expected `<name>_<id>_...`"));
+    }
+}
@@ -0,0 +1,105 @@
+use crate::tutors_deduction::{Deduction, Tally};
+use crate::tutors_late::duration;
+
+/// Builds the "Feedback als Kommentar" text of a single submission, starting with `header`.
+pub fn comment(deductions: &[Deduction], tally: &Tally, header: &str, html: bool) -> String {
+    let breakdown = tally.breakdown(", ");
+    let items: Vec<String> = deductions.iter().map(describe).collect();
+
+    if html {
+        let mut result = format!("<p>{}</p>", escape(header));
+        if !breakdown.is_empty() {
+            result.push_str(&format!("<p>{}</p>", escape(&breakdown)));
+        }
+        if !items.is_empty() {
+            result.push_str("<ul>");
+            for item in items {
+                result.push_str(&format!("<li>{}</li>", escape(&item)));
+            }
+            result.push_str("</ul>");
+        }
+        result
+    } else {
+        let mut lines = vec![header.to_string()];
+        if !breakdown.is_empty() {
+            lines.push(breakdown);
+        }
+        lines.extend(items);
+        lines.join("\n")
+    }
+}
+
+/// Note on a late submission starting with `label`, to be appended to its [`comment`].
+pub fn late(label: &str, minutes: i64, penalty: f32, html: bool) -> String {
+    let text = format!("{} ({}): -{}", label, duration(minutes), penalty);
+    match html {
+        true => format!("<p>{}</p>", escape(&text)),
+        false => format!("\n{}", text),
+    }
+}
+
+/// Formats a deduction as `Main.java:4: -2 [A1] wrong type`
+pub fn describe(deduction: &Deduction) -> String {
+    let points = match (deduction.out_of, deduction.bonus) {
+        (Some(out_of), _) => format!("{}/{}", deduction.points, out_of),
+        (None, true) => format!("+{}", deduction.points),
+        (None, false) => format!("-{}", deduction.points),
+    };
+    let mut text = format!(
+        "{}:{}: {}",
+        deduction.file.display(),
+        deduction.line,
+        points
+    );
+    if let Some(task) = &deduction.task {
+        text.push_str(&format!(" [{}]", task));
+    }
+    if !deduction.reason.is_empty() {
+        text.push(' ');
+        text.push_str(&deduction.reason);
+    }
+    text
+}
+
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::tutors_deduction::{calculate_deduction, tally, TaskLimits};
+    use crate::tutors_language::Languages;
+
+    const FEEDBACK: &str = "Bewertung siehe Feedbackdateien.";
+    const SOURCE: &str = "// Tutor[A1]: -2 use List<T>\nint a; // Tutor: -1\n";
+
+    #[test]
+    fn test_comment() {
+        let languages = Languages::default();
+        let java = languages.patterns("java").unwrap();
+        let deductions =
+            calculate_deduction(Path::new("src/Main.java"), SOURCE.as_bytes(), java).unwrap();
+        let tally = tally(&deductions, &TaskLimits::new());
+
+        assert_eq!(
+            comment(&deductions, &tally, FEEDBACK, false),
+            "Bewertung siehe Feedbackdateien.\nA1: -2\nsrc/Main.java:1: -2 [A1] use List<T>\nsrc/Main.java:2: -1"
+        );
+        assert_eq!(
+            comment(&deductions, &tally, FEEDBACK, true),
+            "<p>Bewertung siehe Feedbackdateien.</p><p>A1: -2</p><ul>\
+            <li>src/Main.java:1: -2 [A1] use List&lt;T&gt;</li><li>src/Main.java:2: -1</li></ul>"
+        );
+    }
+
+    #[test]
+    fn test_comment_without_deductions() {
+        assert_eq!(comment(&[], &Tally::default(), FEEDBACK, false), FEEDBACK);
+    }
+}
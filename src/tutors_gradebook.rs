@@ -0,0 +1,200 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::tutors_csv::Record;
+use crate::tutors_error::{io, Error};
+
+/// Lowest percentage of the total maximum points for each grade, best grade first.
+pub const THRESHOLDS: [(&str, f32); 5] =
+    [("1", 87.5), ("2", 75.), ("3", 62.5), ("4", 50.), ("5", 0.)];
+
+/// Points of every student in every imported assignment, kept as a JSON file.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Gradebook {
+    /// Students by participant ID
+    pub students: BTreeMap<String, Student>,
+    /// Assignments in the order they were first imported
+    pub assignments: Vec<Assignment>,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Student {
+    pub name: String,
+    pub email: String,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Assignment {
+    pub name: String,
+    pub max_points: f32,
+    /// Points by participant ID, students without a grade are left out
+    pub points: BTreeMap<String, f32>,
+}
+
+/// Semester total of a single student.
+#[derive(Debug, PartialEq)]
+pub struct Total<'a> {
+    pub id: &'a str,
+    pub name: &'a str,
+    pub points: f32,
+    pub max_points: f32,
+    /// Number of assignments without a grade
+    pub missing: usize,
+}
+
+impl Total<'_> {
+    pub fn percent(&self) -> f32 {
+        match self.max_points > 0. {
+            true => self.points / self.max_points * 100.,
+            false => 0.,
+        }
+    }
+}
+
+impl Gradebook {
+    /// Reads the gradebook at `path`, or starts an empty one if there is no file yet.
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(path).map_err(io(path))?;
+        let gradebook = serde_json::from_str(&content).map_err(|e| Error::InvalidGradebook {
+            path: path.to_path_buf(),
+            reason: e.to_string(),
+        })?;
+        Ok(gradebook)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let content = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, content + "\n").map_err(io(path))?;
+        Ok(())
+    }
+
+    /// Adds the graded `records` as assignment `name`, replacing an earlier import of the
+    /// same name. Returns whether an assignment was replaced.
+    pub fn import(&mut self, name: &str, records: &[Record]) -> bool {
+        for record in records {
+            self.students.insert(
+                record.id.clone(),
+                Student {
+                    name: record.name.clone(),
+                    email: record.email.clone(),
+                },
+            );
+        }
+        let assignment = Assignment {
+            name: name.to_string(),
+            max_points: records.first().map_or(0., |record| record.max_points),
+            points: records
+                .iter()
+                .filter_map(|record| Some((record.id.clone(), record.points?)))
+                .collect(),
+        };
+
+        match self.assignments.iter_mut().find(|a| a.name == name) {
+            Some(existing) => {
+                *existing = assignment;
+                true
+            }
+            None => {
+                self.assignments.push(assignment);
+                false
+            }
+        }
+    }
+
+    /// Totals over all assignments, sorted by name. Missing grades count as 0 points.
+    pub fn totals(&self) -> Vec<Total<'_>> {
+        let max_points = self.assignments.iter().map(|a| a.max_points).sum();
+        let mut totals: Vec<_> = self
+            .students
+            .iter()
+            .map(|(id, student)| {
+                let points = self.assignments.iter().filter_map(|a| a.points.get(id));
+                Total {
+                    id,
+                    name: &student.name,
+                    points: points.clone().fold(0., |sum, points| sum + points),
+                    max_points,
+                    missing: self.assignments.len() - points.count(),
+                }
+            })
+            .collect();
+        totals.sort_by(|a, b| a.name.cmp(b.name).then(a.id.cmp(b.id)));
+        totals
+    }
+
+    /// Every student without a grade, as `(assignment, participant ID)`.
+    pub fn missing(&self) -> Vec<(&str, &str)> {
+        self.assignments
+            .iter()
+            .flat_map(|a| {
+                self.students
+                    .keys()
+                    .filter(|id| !a.points.contains_key(*id))
+                    .map(|id| (a.name.as_str(), id.as_str()))
+            })
+            .collect()
+    }
+}
+
+/// The best grade whose threshold `percent` reaches, `None` if it is below all of them.
+pub fn grade(percent: f32, thresholds: &[(String, f32)]) -> Option<&str> {
+    let mut thresholds: Vec<_> = thresholds.iter().collect();
+    thresholds.sort_by(|a, b| b.1.total_cmp(&a.1));
+    thresholds
+        .into_iter()
+        .find(|(_, min)| percent >= *min)
+        .map(|(grade, _)| grade.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tutors_csv::test_table;
+
+    #[test]
+    fn test_gradebook() {
+        let mut gradebook = Gradebook::default();
+        let first = test_table(
+            "Teilnehmer/in1,Max,,max@uni,,\"8,00\",\"10,00\",Ja,,,\n\
+             Teilnehmer/in2,Eva,,eva@uni,,\"10,00\",\"10,00\",Ja,,,\n",
+        )
+        .records;
+        let second = test_table(
+            "Teilnehmer/in1,Max,,max@uni,,,\"20,00\",Ja,,,\n\
+             Teilnehmer/in2,Eva,,eva@uni,,\"15,00\",\"20,00\",Ja,,,\n",
+        )
+        .records;
+        assert!(!gradebook.import("A1", &first));
+        assert!(!gradebook.import("A2", &first));
+        assert!(gradebook.import("A2", &second));
+
+        let totals = gradebook.totals();
+        assert_eq!(totals[0].name, "Eva");
+        assert_eq!(totals[0].points, 25.);
+        assert_eq!(totals[1].points, 8.);
+        assert_eq!(totals[1].max_points, 30.);
+        assert_eq!(totals[1].missing, 1);
+        assert_eq!(gradebook.missing(), [("A2", "1")]);
+
+        let json = serde_json::to_string(&gradebook).unwrap();
+        assert_eq!(serde_json::from_str::<Gradebook>(&json).unwrap(), gradebook);
+    }
+
+    #[test]
+    fn test_grade() {
+        let thresholds: Vec<_> = THRESHOLDS
+            .iter()
+            .map(|(grade, min)| (grade.to_string(), *min))
+            .collect();
+        assert_eq!(grade(87.5, &thresholds), Some("1"));
+        assert_eq!(grade(74.9, &thresholds), Some("3"));
+        assert_eq!(grade(0., &thresholds), Some("5"));
+        assert_eq!(grade(40., &thresholds[..4]), None);
+    }
+}
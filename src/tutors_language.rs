@@ -0,0 +1,169 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use regex::Regex;
+
+/// Everything after the comment prefix: optional `[task]`, sign, points, optional `/<out of>`
+/// and the reason. The points are optional so comments without them can be reported.
+const TUTOR_PATTERN: &str =
+    r"[ \t]*Tutor(?:\[([^\]]+)\])?: ([+-])?(\d+(?:\.\d+)?)?(?:/(\d+(?:\.\d+)?))?[ \t]*";
+const DEFAULT_LANGUAGES: [&str; 1] = ["java"];
+
+/// Built-in languages as `(name, extensions, comment syntaxes)`.
+const LANGUAGES: [(&str, &[&str], &[&str]); 13] = [
+    ("java", &["java"], &["//"]),
+    ("c", &["c", "h"], &["//", "/* */"]),
+    ("cpp", &["cpp", "cc", "hpp"], &["//", "/* */"]),
+    ("rust", &["rs"], &["//"]),
+    ("python", &["py"], &["#"]),
+    ("shell", &["sh"], &["#"]),
+    ("haskell", &["hs", "lhs"], &["--", "{- -}"]),
+    ("sql", &["sql"], &["--"]),
+    ("lisp", &["lisp", "scm", "rkt", "clj"], &[";"]),
+    ("asm", &["asm", "s"], &[";"]),
+    ("prolog", &["pl", "pro"], &["%"]),
+    ("matlab", &["m"], &["%"]),
+    ("latex", &["tex"], &["%"]),
+];
+
+/// A line comment (`#`) or a block comment (`/* */`).
+#[derive(Debug, PartialEq, Clone)]
+pub struct CommentSyntax {
+    pub prefix: String,
+    pub suffix: Option<String>,
+}
+
+/// Maps file extensions to the comment syntax tutor comments are written in.
+#[derive(Debug, Clone)]
+pub struct Languages {
+    patterns: HashMap<String, Vec<Regex>>,
+}
+
+impl CommentSyntax {
+    /// Parses `#` or `/* */` (prefix and suffix separated by whitespace).
+    pub fn parse(s: &str) -> Result<Self> {
+        let mut parts = s.split_whitespace();
+        let prefix = parts
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("Empty comment syntax"))?;
+        let suffix = parts.next().map(str::to_string);
+        if parts.next().is_some() {
+            return Err(anyhow::anyhow!("Invalid comment syntax `{}`", s));
+        }
+        Ok(CommentSyntax {
+            prefix: prefix.to_string(),
+            suffix,
+        })
+    }
+
+    fn pattern(&self) -> String {
+        let prefix = regex::escape(&self.prefix);
+        match &self.suffix {
+            Some(suffix) => format!(
+                r"{}{}(.*?)[ \t]*(?:{}.*)?$",
+                prefix,
+                TUTOR_PATTERN,
+                regex::escape(suffix)
+            ),
+            None => format!("{}{}(.*)", prefix, TUTOR_PATTERN),
+        }
+    }
+}
+
+impl Languages {
+    /// Selects built-in languages by name, e.g. `python` or `haskell`.
+    pub fn new(names: &[String]) -> Result<Self> {
+        let mut languages = Languages {
+            patterns: HashMap::new(),
+        };
+        for name in names {
+            let (_, extensions, comments) = LANGUAGES
+                .iter()
+                .find(|(lang, _, _)| lang.eq_ignore_ascii_case(name))
+                .ok_or_else(|| anyhow::anyhow!("Unknown language `{}`", name))?;
+            for ext in extensions.iter() {
+                for comment in comments.iter() {
+                    languages.add(ext, CommentSyntax::parse(comment)?)?;
+                }
+            }
+        }
+
+        Ok(languages)
+    }
+
+    /// Counts files with extension `ext` using the given comment syntax in addition to
+    /// any syntax already registered for it. A syntax registered twice is only used once,
+    /// so its comments aren't counted twice.
+    pub fn add(&mut self, ext: &str, syntax: CommentSyntax) -> Result<()> {
+        let ext = ext.trim_start_matches('.').to_lowercase();
+        let re = Regex::new(&syntax.pattern())?;
+        let patterns = self.patterns.entry(ext).or_default();
+        if !patterns.iter().any(|known| known.as_str() == re.as_str()) {
+            patterns.push(re);
+        }
+        Ok(())
+    }
+
+    /// The tutor comment patterns for files with extension `ext`, if they are counted.
+    ///
+    /// Every pattern uses the same capture groups: task, sign, points, out of, reason.
+    pub fn patterns(&self, ext: &str) -> Option<&[Regex]> {
+        self.patterns.get(&ext.to_lowercase()).map(Vec::as_slice)
+    }
+}
+
+impl Default for Languages {
+    fn default() -> Self {
+        let names: Vec<String> = DEFAULT_LANGUAGES.iter().map(|s| s.to_string()).collect();
+        Languages::new(&names).expect("built-in languages are valid")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default() {
+        let languages = Languages::default();
+
+        assert!(languages.patterns("java").is_some());
+        assert!(languages.patterns("JAVA").is_some());
+        assert!(languages.patterns("py").is_none());
+    }
+
+    #[test]
+    fn test_block_comment() {
+        let languages = Languages::new(&["c".to_string()]).unwrap();
+        let re = &languages.patterns("c").unwrap()[1];
+
+        let cap = re
+            .captures("int a; /* Tutor[A1]: -2 wrong type */")
+            .unwrap();
+        let cap = cap
+            .iter()
+            .flatten()
+            .skip(1)
+            .map(|m| m.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(cap, ["A1", "-", "2", "wrong type"]);
+    }
+
+    #[test]
+    fn test_custom_syntax() {
+        let mut languages = Languages::new(&[]).unwrap();
+        languages
+            .add(".erl", CommentSyntax::parse("%").unwrap())
+            .unwrap();
+
+        assert!(languages.patterns("erl").unwrap()[0].is_match("%Tutor: -1"));
+
+        let mut languages = Languages::default();
+        languages
+            .add("java", CommentSyntax::parse("//").unwrap())
+            .unwrap();
+        assert_eq!(languages.patterns("java").unwrap().len(), 1);
+        assert!(CommentSyntax::parse("/* */ x").is_err());
+        assert!(Languages::new(&["cobol".to_string()]).is_err());
+    }
+}
@@ -0,0 +1,219 @@
+use regex::Regex;
+use serde::Deserialize;
+
+/// Minutes since 1970-01-01 00:00, in the local time the dates are written in.
+pub type Timestamp = i64;
+
+/// Month names of the German and English dates, matched by their first three letters.
+const MONTHS: [&[&str]; 12] = [
+    &["jan"],
+    &["feb"],
+    &["mär", "mae", "mrz", "mar"],
+    &["apr"],
+    &["mai", "may"],
+    &["jun"],
+    &["jul"],
+    &["aug"],
+    &["sep"],
+    &["okt", "oct"],
+    &["nov"],
+    &["dez", "dec"],
+];
+
+/// What a late penalty is counted in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Unit {
+    Hour,
+    #[default]
+    Day,
+}
+
+impl Unit {
+    fn minutes(self) -> i64 {
+        match self {
+            Unit::Hour => 60,
+            Unit::Day => 24 * 60,
+        }
+    }
+}
+
+impl std::str::FromStr for Unit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "hour" => Ok(Unit::Hour),
+            "day" => Ok(Unit::Day),
+            _ => Err(format!("expected hour or day, got `{}`", s)),
+        }
+    }
+}
+
+/// Deadline of an assignment and the points deducted for submitting after it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LatePolicy {
+    pub deadline: Timestamp,
+    /// Minutes after the deadline that still count as on time
+    pub grace: i64,
+    /// Points deducted per started `per` after the deadline, 0 to only report
+    pub penalty: f32,
+    pub per: Unit,
+    /// Highest total penalty
+    pub cap: Option<f32>,
+}
+
+impl LatePolicy {
+    /// Minutes `submitted` is after the deadline, `None` if it is within the grace period.
+    pub fn lateness(&self, submitted: Timestamp) -> Option<i64> {
+        let minutes = submitted - self.deadline;
+        (minutes > self.grace).then_some(minutes)
+    }
+
+    /// Points deducted for `minutes` of lateness, counted from the deadline.
+    pub fn penalty(&self, minutes: i64) -> f32 {
+        let unit = self.per.minutes();
+        let units = (minutes.max(0) + unit - 1) / unit;
+        let penalty = units as f32 * self.penalty;
+        match self.cap {
+            Some(cap) => penalty.min(cap),
+            None => penalty,
+        }
+    }
+}
+
+/// Reads a date as Moodle writes it, e.g. `Mittwoch, 13. März 2024, 10:15` or
+/// `Wednesday, 13 March 2024, 10:15 AM`, or as `2024-03-13 10:15`.
+pub fn parse_date(text: &str) -> Option<Timestamp> {
+    let text = text.trim();
+    let iso = Regex::new(r"^(\d{4})-(\d{1,2})-(\d{1,2})[ T](\d{1,2}):(\d{2})$").ok()?;
+    if let Some(caps) = iso.captures(text) {
+        let number = |i: usize| caps[i].parse::<i64>().ok();
+        return timestamp(number(1)?, number(2)?, number(3)?, number(4)?, number(5)?);
+    }
+
+    let long = Regex::new(
+        r"^(?:\p{L}+,\s*)?(?:(?P<d1>\d{1,2})\.?\s+(?P<m1>\p{L}+)\.?|(?P<m2>\p{L}+)\.?\s+(?P<d2>\d{1,2}),?)\s+(?P<y>\d{4}),?\s+(?P<h>\d{1,2}):(?P<min>\d{2})(?:\s*(?P<ampm>[AaPp])\.?[Mm]\.?)?$",
+    )
+    .ok()?;
+    let caps = long.captures(text)?;
+    let day = caps.name("d1").or(caps.name("d2"))?.as_str().parse().ok()?;
+    let month = month(caps.name("m1").or(caps.name("m2"))?.as_str())?;
+    let year = caps["y"].parse().ok()?;
+    let mut hour: i64 = caps["h"].parse().ok()?;
+    if let Some(ampm) = caps.name("ampm") {
+        if !(1..=12).contains(&hour) {
+            return None;
+        }
+        hour %= 12;
+        if ampm.as_str().eq_ignore_ascii_case("p") {
+            hour += 12;
+        }
+    }
+    timestamp(year, month, day, hour, caps["min"].parse().ok()?)
+}
+
+/// Formats minutes of lateness as `26:05 h`.
+pub fn duration(minutes: i64) -> String {
+    format!("{}:{:02} h", minutes / 60, minutes % 60)
+}
+
+fn month(name: &str) -> Option<i64> {
+    let prefix: String = name.to_lowercase().chars().take(3).collect();
+    let index = MONTHS
+        .iter()
+        .position(|names| names.contains(&prefix.as_str()))?;
+    Some(index as i64 + 1)
+}
+
+fn timestamp(year: i64, month: i64, day: i64, hour: i64, minute: i64) -> Option<Timestamp> {
+    if !(1..=12).contains(&month)
+        || !(1..=days_in_month(year, month)).contains(&day)
+        || hour > 23
+        || minute > 59
+    {
+        return None;
+    }
+    Some((days_from_civil(year, month, day) * 24 + hour) * 60 + minute)
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days since 1970-01-01 of a date in the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_date() {
+        let expected = timestamp(2024, 3, 13, 10, 15);
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(2024, 3, 13), 19795);
+
+        assert_eq!(parse_date("Mittwoch, 13. März 2024, 10:15"), expected);
+        assert_eq!(parse_date("Wednesday, 13 March 2024, 10:15 AM"), expected);
+        assert_eq!(parse_date("Wednesday, March 13, 2024, 10:15 AM"), expected);
+        assert_eq!(parse_date("2024-03-13 10:15"), expected);
+        assert_eq!(
+            parse_date("Friday, 1 November 2024, 12:05 AM"),
+            timestamp(2024, 11, 1, 0, 5)
+        );
+        assert_eq!(
+            parse_date("Dienstag, 31. Dezember 2024, 23:59"),
+            parse_date("Tuesday, 31 December 2024, 11:59 PM")
+        );
+
+        assert_eq!(parse_date(""), None);
+        assert_eq!(parse_date("-"), None);
+        assert_eq!(parse_date("Mittwoch, 13. Foo 2024, 10:15"), None);
+        assert_eq!(parse_date("2024-13-01 10:15"), None);
+        assert_eq!(parse_date("2024-04-31 10:15"), None);
+        assert_eq!(parse_date("2023-02-29 10:15"), None);
+        assert_eq!(parse_date("1900-02-29 10:15"), None);
+        assert!(parse_date("2024-02-29 10:15").is_some());
+        assert!(parse_date("2000-02-29 10:15").is_some());
+    }
+
+    #[test]
+    fn test_penalty() {
+        let policy = LatePolicy {
+            deadline: parse_date("2024-03-13 23:59").unwrap(),
+            grace: 10,
+            penalty: 1.5,
+            per: Unit::Day,
+            cap: Some(4.),
+        };
+        let late = |date| policy.lateness(parse_date(date).unwrap());
+
+        assert_eq!(late("2024-03-13 20:00"), None);
+        assert_eq!(late("2024-03-14 00:09"), None);
+        assert_eq!(late("2024-03-14 00:10"), Some(11));
+        assert_eq!(policy.penalty(11), 1.5);
+        assert_eq!(policy.penalty(24 * 60 + 1), 3.);
+        assert_eq!(policy.penalty(10 * 24 * 60), 4.);
+
+        let hourly = LatePolicy {
+            per: Unit::Hour,
+            cap: None,
+            ..policy
+        };
+        assert_eq!(hourly.penalty(10 * 60), 15.);
+        assert_eq!(duration(26 * 60 + 5), "26:05 h");
+    }
+}
@@ -0,0 +1,337 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+
+use pdf_writer::{Content, Finish, Name, Pdf, Rect, Ref, Str};
+
+use crate::tutors_feedback::{describe, escape};
+use crate::tutorslib::Graded;
+
+/// A4 in points
+const PAGE_WIDTH: f32 = 595.;
+const PAGE_HEIGHT: f32 = 842.;
+const MARGIN: f32 = 56.;
+const FONT_SIZE: f32 = 9.;
+const LEADING: f32 = 12.;
+/// Characters of Courier at `FONT_SIZE` that fit between the margins
+const LINE_WIDTH: usize = 88;
+
+/// Feedback page of a single submission: the score, subtotals per task and every tutor
+/// comment together with the code around it.
+#[derive(Debug, PartialEq)]
+pub struct Report {
+    pub title: String,
+    pub points: f32,
+    pub max_points: f32,
+    pub subtotals: Vec<String>,
+    pub items: Vec<Item>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Item {
+    /// The comment as in the feedback text, e.g. `Main.java:4: -2 [A1] wrong type`
+    pub description: String,
+    /// 1-based line number of the comment
+    pub line: usize,
+    /// Numbered source lines around the comment, empty if the file can't be read
+    pub context: Vec<(usize, String)>,
+}
+
+impl Report {
+    /// Builds the report of `graded`, showing `context` lines before and after each comment.
+    pub fn new(graded: &Graded, max_points: f32, context: usize) -> Self {
+        let mut sources: HashMap<PathBuf, Vec<String>> = HashMap::new();
+        let items = graded
+            .deductions
+            .iter()
+            .map(|deduction| {
+                let lines = sources.entry(deduction.file.clone()).or_insert_with(|| {
+                    std::fs::read(graded.path.join(&deduction.file))
+                        .map(|bytes| {
+                            let text = String::from_utf8_lossy(&bytes);
+                            text.lines()
+                                .map(|line| line.replace('\t', "    "))
+                                .collect()
+                        })
+                        .unwrap_or_default()
+                });
+                let first = deduction.line.saturating_sub(context).max(1);
+                let last = (deduction.line + context).min(lines.len());
+                Item {
+                    description: describe(deduction),
+                    line: deduction.line,
+                    context: (first..=last).map(|n| (n, lines[n - 1].clone())).collect(),
+                }
+            })
+            .collect();
+
+        Report {
+            title: graded
+                .path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            points: graded.points,
+            max_points,
+            subtotals: subtotals(graded),
+            items,
+        }
+    }
+
+    pub fn html(&self) -> String {
+        let mut html = format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Feedback {}</title>\n\
+            <style>\nbody {{ font-family: sans-serif; max-width: 60em; margin: 2em auto; }}\n\
+            pre {{ background: #f6f6f6; padding: .5em; overflow-x: auto; }}\n\
+            mark {{ background: #ffe08a; display: block; }}\n</style>\n</head>\n<body>\n\
+            <h1>Feedback</h1>\n<p>{}</p>\n<p><strong>Points: {} / {}</strong></p>\n",
+            escape(&self.title),
+            escape(&self.title),
+            self.points,
+            self.max_points
+        );
+        if !self.subtotals.is_empty() {
+            html.push_str("<ul>\n");
+            for subtotal in &self.subtotals {
+                html.push_str(&format!("<li>{}</li>\n", escape(subtotal)));
+            }
+            html.push_str("</ul>\n");
+        }
+        for item in &self.items {
+            html.push_str(&format!("<h2>{}</h2>\n", escape(&item.description)));
+            if item.context.is_empty() {
+                continue;
+            }
+            html.push_str("<pre>");
+            for (n, line) in &item.context {
+                let line = format!("{:>4} | {}", n, escape(line));
+                match *n == item.line {
+                    true => html.push_str(&format!("<mark>{}</mark>", line)),
+                    false => html.push_str(&format!("{}\n", line)),
+                }
+            }
+            html.push_str("</pre>\n");
+        }
+        html.push_str("</body>\n</html>\n");
+        html
+    }
+
+    /// Renders the report as plain text pages using the built-in Courier fonts.
+    pub fn pdf(&self) -> Vec<u8> {
+        let lines = self.lines();
+        let per_page = ((PAGE_HEIGHT - 2. * MARGIN) / LEADING) as usize;
+        let pages: Vec<_> = lines.chunks(per_page.max(1)).collect();
+
+        let catalog_id = Ref::new(1);
+        let page_tree_id = Ref::new(2);
+        let regular_id = Ref::new(3);
+        let bold_id = Ref::new(4);
+        let page_ids: Vec<_> = (0..pages.len())
+            .map(|i| Ref::new(5 + 2 * i as i32))
+            .collect();
+
+        let mut pdf = Pdf::new();
+        pdf.catalog(catalog_id).pages(page_tree_id);
+        pdf.pages(page_tree_id)
+            .kids(page_ids.iter().copied())
+            .count(pages.len() as i32);
+        for (id, font) in [(regular_id, "Courier"), (bold_id, "Courier-Bold")] {
+            pdf.type1_font(id)
+                .base_font(Name(font.as_bytes()))
+                .encoding_predefined(Name(b"WinAnsiEncoding"));
+        }
+
+        for (page_lines, page_id) in pages.iter().zip(&page_ids) {
+            let content_id = Ref::new(page_id.get() + 1);
+            let mut page = pdf.page(*page_id);
+            page.media_box(Rect::new(0., 0., PAGE_WIDTH, PAGE_HEIGHT));
+            page.parent(page_tree_id);
+            page.contents(content_id);
+            let mut resources = page.resources();
+            let mut fonts = resources.fonts();
+            fonts.pair(Name(b"F1"), regular_id);
+            fonts.pair(Name(b"F2"), bold_id);
+            fonts.finish();
+            resources.finish();
+            page.finish();
+
+            let mut content = Content::new();
+            content.begin_text();
+            content.set_leading(LEADING);
+            content.next_line(MARGIN, PAGE_HEIGHT - MARGIN);
+            for (bold, line) in page_lines.iter() {
+                let font = if *bold { Name(b"F2") } else { Name(b"F1") };
+                content.set_font(font, FONT_SIZE);
+                content.show(Str(&win_ansi(line)));
+                content.next_line_using_leading();
+            }
+            content.end_text();
+            pdf.stream(content_id, &content.finish());
+        }
+
+        pdf.finish()
+    }
+
+    /// The text of the PDF, wrapped to the page width and flagged if it's a heading.
+    fn lines(&self) -> Vec<(bool, String)> {
+        let mut lines = vec![
+            (true, "Feedback".to_string()),
+            (false, self.title.clone()),
+            (false, String::new()),
+            (
+                true,
+                format!("Points: {} / {}", self.points, self.max_points),
+            ),
+        ];
+        lines.extend(self.subtotals.iter().map(|s| (false, format!("  {}", s))));
+        for item in &self.items {
+            lines.push((false, String::new()));
+            lines.push((true, item.description.clone()));
+            for (n, line) in &item.context {
+                let marker = if *n == item.line { '>' } else { ' ' };
+                lines.push((false, format!("{}{:>4} | {}", marker, n, line)));
+            }
+        }
+
+        lines
+            .into_iter()
+            .flat_map(|(bold, line)| wrap(&line).into_iter().map(move |l| (bold, l)))
+            .collect()
+    }
+}
+
+/// Per-task subtotals such as `A1: -2` or `A2: 3/5`, followed by untagged deductions and bonus.
+fn subtotals(graded: &Graded) -> Vec<String> {
+    let mut awarded: BTreeMap<Option<&str>, (f32, f32)> = BTreeMap::new();
+    for deduction in &graded.deductions {
+        if let (Some(out_of), false) = (deduction.out_of, deduction.bonus) {
+            let entry = awarded.entry(deduction.task.as_deref()).or_default();
+            entry.0 += deduction.points.min(out_of);
+            entry.1 += out_of;
+        }
+    }
+
+    let tally = &graded.tally;
+    let mut tasks: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+    for (task, points) in &tally.per_task {
+        tasks.entry(task).or_default().push(format!("-{}", points));
+    }
+    for (task, (awarded, available)) in &awarded {
+        if let Some(task) = task {
+            tasks
+                .entry(task)
+                .or_default()
+                .push(format!("{}/{}", awarded, available));
+        }
+    }
+
+    let mut rows: Vec<String> = tasks
+        .into_iter()
+        .map(|(task, parts)| format!("{}: {}", task, parts.join(", ")))
+        .collect();
+
+    let mut other = Vec::new();
+    let untagged = tally.total - tally.per_task.values().sum::<f32>();
+    if untagged > 0. {
+        other.push(format!("-{}", untagged));
+    }
+    if let Some((awarded, available)) = awarded.get(&None) {
+        other.push(format!("{}/{}", awarded, available));
+    }
+    if !other.is_empty() {
+        rows.push(format!("Other: {}", other.join(", ")));
+    }
+    if tally.bonus > 0. {
+        rows.push(format!("Bonus: +{}", tally.bonus));
+    }
+    rows
+}
+
+fn wrap(line: &str) -> Vec<String> {
+    let chars: Vec<char> = line.chars().collect();
+    if chars.is_empty() {
+        return vec![String::new()];
+    }
+    chars
+        .chunks(LINE_WIDTH)
+        .map(|chunk| chunk.iter().collect())
+        .collect()
+}
+
+/// Encodes `text` for the standard fonts, replacing characters outside of Latin-1 with `?`.
+fn win_ansi(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| match c as u32 {
+            0x20..=0x7e | 0xa0..=0xff => c as u8,
+            _ => b'?',
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::tutors_deduction::{Deduction, Grading};
+    use crate::tutorslib::grade;
+
+    const SOURCE: &str = "class Main {\n    // Tutor[A1]: -2 wrong type\n    int a;\n    // Tutor[A2]: 3/5\n    // Tutor: +1 nice\n}\n";
+
+    #[test]
+    fn test_report() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().to_path_buf();
+        std::fs::write(dir.join("Main.java"), SOURCE).unwrap();
+
+        let graded = grade(&dir, 10., &Grading::default()).unwrap();
+        let report = Report::new(&graded, 10., 1);
+
+        assert_eq!(report.points, 7.);
+        assert_eq!(report.subtotals, ["A1: -2", "A2: 3/5", "Bonus: +1"]);
+        assert_eq!(
+            report.items[0].description,
+            "Main.java:2: -2 [A1] wrong type"
+        );
+        assert_eq!(
+            report.items[0].context,
+            [
+                (1, "class Main {".to_string()),
+                (2, "    // Tutor[A1]: -2 wrong type".to_string()),
+                (3, "    int a;".to_string()),
+            ]
+        );
+        assert_eq!(report.items[2].context.len(), 3);
+
+        let html = report.html();
+        assert!(html.contains("<mark>   2 |     // Tutor[A1]: -2 wrong type</mark>"));
+        assert!(html.contains("<li>A2: 3/5</li>"));
+
+        let pdf = report.pdf();
+        assert!(pdf.starts_with(b"%PDF-"));
+    }
+
+    #[test]
+    fn test_missing_source() {
+        let deduction = Deduction {
+            task: None,
+            points: 1.,
+            bonus: false,
+            out_of: None,
+            reason: String::new(),
+            file: PathBuf::from("Main.java"),
+            line: 3,
+        };
+        let graded = Graded::new(
+            Path::new("missing"),
+            vec![deduction],
+            10.,
+            &Grading::default(),
+        );
+        let report = Report::new(&graded, 10., 2);
+
+        assert_eq!(report.items[0].context, []);
+        assert_eq!(report.subtotals, ["Other: -1"]);
+        assert_eq!(win_ansi("Prüfung →"), b"Pr\xfcfung ?");
+        assert_eq!(wrap(&"x".repeat(LINE_WIDTH + 1)).len(), 2);
+    }
+}
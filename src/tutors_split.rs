@@ -0,0 +1,150 @@
+use serde::Deserialize;
+
+/// How `split` assigns the submissions to tutors.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Strategy {
+    /// One after another in the order of the folder names
+    #[default]
+    RoundRobin,
+    /// Largest submission first, each to the tutor with the fewest bytes so far
+    Size,
+    /// Round-robin over the submissions shuffled with the seed
+    Seeded,
+}
+
+impl std::str::FromStr for Strategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "round-robin" => Ok(Strategy::RoundRobin),
+            "size" => Ok(Strategy::Size),
+            "seeded" => Ok(Strategy::Seeded),
+            _ => Err(format!("expected round-robin, size or seeded, got `{}`", s)),
+        }
+    }
+}
+
+/// How `split` hands out the submission folders.
+#[derive(Debug, Clone, Default)]
+pub struct SplitOptions {
+    pub strategy: Strategy,
+    /// Seed of [`Strategy::Seeded`]
+    pub seed: u64,
+    /// Copy the folders instead of moving them
+    pub copy: bool,
+    /// Write a zip per tutor and leave the folders in place
+    pub zip: bool,
+}
+
+/// A submission folder to assign, as `(name, bytes)`.
+pub type Submission = (String, u64);
+
+/// The index of the tutor for each of `submissions`, out of `tutors` tutors.
+///
+/// The result only depends on the names and sizes, not on the order of `submissions`.
+pub fn assign(
+    submissions: &[Submission],
+    tutors: usize,
+    strategy: Strategy,
+    seed: u64,
+) -> Vec<usize> {
+    let mut assigned = vec![0; submissions.len()];
+    if tutors == 0 {
+        return assigned;
+    }
+
+    let mut order: Vec<usize> = (0..submissions.len()).collect();
+    order.sort_by(|&a, &b| submissions[a].0.cmp(&submissions[b].0));
+
+    match strategy {
+        Strategy::RoundRobin => {
+            for (n, &i) in order.iter().enumerate() {
+                assigned[i] = n % tutors;
+            }
+        }
+        Strategy::Size => {
+            order.sort_by(|&a, &b| submissions[b].1.cmp(&submissions[a].1));
+            let mut loads = vec![0; tutors];
+            for &i in &order {
+                let tutor = (0..tutors).min_by_key(|&t| loads[t]).unwrap_or(0);
+                loads[tutor] += submissions[i].1;
+                assigned[i] = tutor;
+            }
+        }
+        Strategy::Seeded => {
+            let mut rng = SplitMix64(seed);
+            for n in (1..order.len()).rev() {
+                let m = (rng.next() % (n as u64 + 1)) as usize;
+                order.swap(n, m);
+            }
+            for (n, &i) in order.iter().enumerate() {
+                assigned[i] = n % tutors;
+            }
+        }
+    }
+    assigned
+}
+
+/// Small PRNG so the same seed gives the same split on every platform and version.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn submissions(sizes: &[u64]) -> Vec<Submission> {
+        sizes
+            .iter()
+            .enumerate()
+            .map(|(i, size)| (format!("S{}_{}_assignsubmission_file_", i, i), *size))
+            .collect()
+    }
+
+    #[test]
+    fn test_round_robin() {
+        let mut subs = submissions(&[1, 1, 1, 1, 1]);
+        assert_eq!(assign(&subs, 2, Strategy::RoundRobin, 0), [0, 1, 0, 1, 0]);
+
+        subs.reverse();
+        assert_eq!(assign(&subs, 2, Strategy::RoundRobin, 0), [0, 1, 0, 1, 0]);
+    }
+
+    #[test]
+    fn test_size() {
+        let subs = submissions(&[100, 10, 60, 40, 30, 20]);
+        let assigned = assign(&subs, 2, Strategy::Size, 0);
+        assert_eq!(assigned, [0, 1, 1, 1, 0, 1]);
+
+        let mut loads = [0; 2];
+        for (tutor, (_, size)) in assigned.iter().zip(&subs) {
+            loads[*tutor] += size;
+        }
+        assert_eq!(loads, [130, 130]);
+    }
+
+    #[test]
+    fn test_seeded() {
+        let subs = submissions(&[1; 12]);
+        let first = assign(&subs, 3, Strategy::Seeded, 42);
+        assert_eq!(first, assign(&subs, 3, Strategy::Seeded, 42));
+        assert_ne!(first, assign(&subs, 3, Strategy::Seeded, 7));
+        for tutor in 0..3 {
+            assert_eq!(first.iter().filter(|&&t| t == tutor).count(), 4);
+        }
+
+        assert_eq!("Round-Robin".parse(), Ok(Strategy::RoundRobin));
+        assert!("random".parse::<Strategy>().is_err());
+    }
+}
@@ -0,0 +1,123 @@
+use std::fmt;
+
+const HISTOGRAM_BUCKETS: usize = 10;
+const HISTOGRAM_WIDTH: usize = 40;
+
+/// Summary of a grading round, all values in points except `pass_rate`.
+#[derive(Debug, PartialEq)]
+pub struct Summary {
+    pub count: usize,
+    pub ungraded: usize,
+    pub mean: f32,
+    pub median: f32,
+    pub std_dev: f32,
+    pub min: f32,
+    pub max: f32,
+    pub pass_rate: f32,
+    /// Number of scores per tenth of the maximum points; the last bucket includes 100%.
+    pub histogram: [usize; HISTOGRAM_BUCKETS],
+}
+
+/// A single graded submission as `(points, max_points)`.
+pub type Score = (f32, f32);
+
+pub fn summarize(scores: &[Score], ungraded: usize, pass_ratio: f32) -> Option<Summary> {
+    if scores.is_empty() {
+        return None;
+    }
+
+    let count = scores.len();
+    let mut points: Vec<f32> = scores.iter().map(|(p, _)| *p).collect();
+    points.sort_by(f32::total_cmp);
+
+    let mean = points.iter().sum::<f32>() / count as f32;
+    let median = if count.is_multiple_of(2) {
+        (points[count / 2 - 1] + points[count / 2]) / 2.
+    } else {
+        points[count / 2]
+    };
+    let variance = points.iter().map(|p| (p - mean).powi(2)).sum::<f32>() / count as f32;
+
+    let passed = scores
+        .iter()
+        .filter(|(p, max)| *p >= max * pass_ratio)
+        .count();
+
+    let mut histogram = [0; HISTOGRAM_BUCKETS];
+    for (p, max) in scores {
+        let ratio = if *max > 0. { p / max } else { 0. };
+        let bucket = (ratio * HISTOGRAM_BUCKETS as f32).floor().max(0.) as usize;
+        histogram[bucket.min(HISTOGRAM_BUCKETS - 1)] += 1;
+    }
+
+    Some(Summary {
+        count,
+        ungraded,
+        mean,
+        median,
+        std_dev: variance.sqrt(),
+        min: points[0],
+        max: points[count - 1],
+        pass_rate: passed as f32 / count as f32,
+        histogram,
+    })
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:9}: {}", "Graded", self.count)?;
+        if self.ungraded > 0 {
+            writeln!(f, "{:9}: {}", "Ungraded", self.ungraded)?;
+        }
+        writeln!(f, "{:9}: {:.2}", "Mean", self.mean)?;
+        writeln!(f, "{:9}: {:.2}", "Median", self.median)?;
+        writeln!(f, "{:9}: {:.2}", "Std dev", self.std_dev)?;
+        writeln!(f, "{:9}: {:.2}", "Min", self.min)?;
+        writeln!(f, "{:9}: {:.2}", "Max", self.max)?;
+        writeln!(f, "{:9}: {:.1}%", "Pass rate", self.pass_rate * 100.)?;
+        writeln!(f)?;
+
+        let highest = self.histogram.iter().copied().max().unwrap_or(0).max(1);
+        for (i, count) in self.histogram.iter().enumerate() {
+            let step = 100 / HISTOGRAM_BUCKETS;
+            let upper = if i == HISTOGRAM_BUCKETS - 1 { "]" } else { ")" };
+            let bar = "#".repeat(count * HISTOGRAM_WIDTH / highest);
+            let line = format!(
+                "[{:3}%, {:3}%{} {:4} {}",
+                i * step,
+                (i + 1) * step,
+                upper,
+                count,
+                bar
+            );
+            writeln!(f, "{}", line.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summarize() {
+        let scores = [(10., 20.), (20., 20.), (5., 20.), (15., 20.)];
+        let summary = summarize(&scores, 1, 0.5).unwrap();
+
+        assert_eq!(summary.count, 4);
+        assert_eq!(summary.ungraded, 1);
+        assert_eq!(summary.mean, 12.5);
+        assert_eq!(summary.median, 12.5);
+        assert_eq!(summary.min, 5.);
+        assert_eq!(summary.max, 20.);
+        assert_eq!(summary.pass_rate, 0.75);
+        assert_eq!(summary.histogram, [0, 0, 1, 0, 0, 1, 0, 1, 0, 1]);
+        assert!((summary.std_dev - 5.5902).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_summarize_empty() {
+        assert_eq!(summarize(&[], 3, 0.5), None);
+    }
+}
@@ -1,249 +1,1516 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs::{create_dir, File};
-use std::io::{BufRead, BufReader, Read, Write};
+use std::io::{BufReader, Cursor, Read, Seek, Write};
 use std::path::Path;
 use std::path::PathBuf;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use regex::Regex;
+use serde::Deserialize;
 use walkdir::{DirEntry, WalkDir};
-use zip::ZipArchive;
 
-use crate::tutors_csv::Record;
+use crate::tutors_archive::{extract, has_archive_extension, list, ArchiveKind, Budget, Limits};
+use crate::tutors_csv::{read_table, write_table, Locale, Record, Rejected, Table};
+use crate::tutors_deduction::{calculate_deduction, tally, Deduction, Grading, Tally};
+use crate::tutors_error::{io, Error, Problems};
+use crate::tutors_feedback;
+use crate::tutors_gradebook::{self, Gradebook};
+use crate::tutors_language::Languages;
+use crate::tutors_late::{self, LatePolicy};
+use crate::tutors_report::Report;
+use crate::tutors_split::{assign, SplitOptions, Submission};
+use crate::tutors_stats;
 
-const COUNTED_FILES: [&str; 1] = ["java"];
 const DEFAULT_MAX_POINTS: u8 = 25;
 const ID_PATTERN: &str = r"([\d]+)";
-const IGNORED_NAMES: [&str; 6] = ["__macosx", ".git", ".idea", ".ds_store", ".iml", ".class"];
-const NAME_PATTERN: &str = r"([^\d_]*)";
-const TUTOR_PATTERN: &str = r"// Tutor: (-)?(\d*(\.\d)?)";
-
-const FEEDBACK: &str = "Bewertung siehe Feedbackdateien.";
-
-pub fn count(path: &Path, target_dir: &Path, max_points: &Option<u8>, _debug: bool) -> Result<()> {
-    //dbg!(&path, max_points);
+/// Suffix of the folders Moodle expects in an uploaded feedback zip.
+const SUBMISSION_SUFFIX: &str = "_assignsubmission_file_";
+
+/// Grades every submission folder in `path` and writes `result.csv` into `target_dir`, with
+/// a `name,points,breakdown` row per folder.
+///
+/// `max_points` defaults to 25.
+pub fn count(
+    path: &Path,
+    target_dir: &Path,
+    max_points: &Option<u8>,
+    grading: &Grading,
+    _debug: bool,
+) -> Result<()> {
     let max_points = match max_points {
         Some(points) => points,
         None => &DEFAULT_MAX_POINTS,
     };
 
     if !target_dir.exists() {
-        create_dir(target_dir)?;
+        create_dir(target_dir).map_err(io(target_dir))?;
+    }
+
+    let folders = WalkDir::new(path)
+        .max_depth(1)
+        .into_iter()
+        .skip(1)
+        .flatten()
+        .filter(|entry| entry.path().is_dir());
+    let result_path = target_dir.join("result.csv");
+    let mut result = csv::Writer::from_path(&result_path).map_err(|e| Error::InvalidTable {
+        path: result_path.clone(),
+        reason: e.to_string(),
+    })?;
+    let mut problems = Problems::default();
+
+    for folder in folders {
+        let folder = folder.path();
+        let folder_name = match folder.file_name() {
+            Some(name) => name.to_string_lossy(),
+            None => continue,
+        };
+        let name = match moodle_name(&folder_name) {
+            Some(name) => name,
+            None => {
+                problems.push(Error::UnmatchedSubmission(folder.to_path_buf()));
+                continue;
+            }
+        };
+
+        let graded = match problems.check(grade(folder, *max_points as f32, grading)) {
+            Some(graded) => graded,
+            None => continue,
+        };
+        if let Some(problem) = &graded.problem {
+            warn(&folder_name, problem);
+        }
+
+        let points = graded.points.to_string();
+        let breakdown = graded.tally.breakdown("; ");
+        result.write_record([name, &points, &breakdown])?;
+    }
+    result.flush().map_err(io(&result_path))?;
+
+    Ok(problems.finish()?)
+}
+
+/// Extracts the archive of all submissions at `path` into `target`, by default a folder
+/// named after the archive, and then the archives inside every submission.
+///
+/// The archives of a submission, nested ones included, are extracted within `limits`
+/// together. A submission exceeding them is reported and everything extracted for it is
+/// removed again. Files matching `ignored` are removed afterwards and the submissions are
+/// flattened if `flatten` is set. With `single` only the outer archive is extracted.
+///
+/// In `dry_run` mode nothing is written, the outer archive is only listed to print the
+/// files it contains, the nested archives and what is removed and moved afterwards. The
+/// content of nested archives is unknown until they are extracted.
+#[allow(clippy::too_many_arguments)]
+pub fn unzip(
+    path: &Path,
+    single: bool,
+    flatten: bool,
+    target: Option<&PathBuf>,
+    ignored: &[String],
+    limits: &Limits,
+    debug: bool,
+    dry_run: bool,
+) -> Result<()> {
+    let file_name = match path.file_stem() {
+        Some(stem) => Path::new(".").join(stem),
+        None => {
+            return Err(Error::BadArchive {
+                path: path.to_path_buf(),
+                reason: "no file name".to_string(),
+            }
+            .into())
+        }
+    };
+    let target = match target {
+        Some(path_buf) => path_buf.as_path(),
+        None => &file_name,
+    };
+
+    // The outer archive holds all submissions, only the ratio is checked
+    let kind = ArchiveKind::detect(path).ok_or_else(|| Error::BadArchive {
+        path: path.to_path_buf(),
+        reason: "unsupported format".to_string(),
+    })?;
+    if dry_run {
+        let files = list(path, kind)?;
+        plan("Extract", path, Some(target));
+        for (action, path, to) in unzip_plan(&files, target, single, flatten, ignored) {
+            plan(action, &path, to.as_deref());
+        }
+        return Ok(());
+    }
+    let mut budget = Budget::unlimited();
+    let files = extract_reporting(path, kind, target, limits, &mut budget, debug)?;
+
+    // No more work to be done in single mode
+    if single {
+        //std::fs::remove_file(path)?;
+        return Ok(());
+    }
+
+    let mut problems = Problems::default();
+    for (submission, archives) in submissions(target, &files) {
+        // All archives of a submission share its limits
+        let mut budget = Budget::new(limits);
+        let before: HashSet<PathBuf> = WalkDir::new(&submission)
+            .into_iter()
+            .flatten()
+            .map(DirEntry::into_path)
+            .collect();
+        let mut exceeded = None;
+        for (path, kind) in &archives {
+            let target = path.parent().unwrap_or(target);
+            dbglog!(debug, "Unzipping", "path", target.to_str().unwrap_or(""));
+
+            if let Err(err) = unzip_nested(path, *kind, target, 1, limits, &mut budget, debug) {
+                match err.downcast::<Error>() {
+                    Ok(Error::LimitExceeded(limit)) => {
+                        exceeded = Some(limit);
+                        break;
+                    }
+                    Ok(err) => problems.push(err),
+                    Err(err) => problems.push(err),
+                }
+            }
+        }
+        if let Some(limit) = exceeded {
+            warn(
+                submission.to_str().unwrap_or(""),
+                &format!("extraction aborted, {}", limit),
+            );
+            problems.check(remove_added(&submission, &before));
+            continue;
+        }
+
+        let mut cleaned: Vec<&Path> = Vec::new();
+        for (path, _) in &archives {
+            let target = path.parent().unwrap_or(target);
+            if cleaned.contains(&target) {
+                continue;
+            }
+            cleaned.push(target);
+            problems.check(clean_dirs(target, ignored, debug, false));
+
+            if flatten {
+                problems.check(flatten_dirs(target, None, ignored, debug, false));
+            }
+        }
+    }
+
+    Ok(problems.finish()?)
+}
+
+/// The archives in `files` grouped by the submission folder below `target` they belong to.
+fn submissions<'a>(
+    target: &Path,
+    files: &'a [PathBuf],
+) -> Vec<(PathBuf, Vec<(&'a PathBuf, ArchiveKind)>)> {
+    let mut submissions: Vec<(PathBuf, Vec<_>)> = Vec::new();
+    for (path, kind) in archives(files) {
+        let relative = path.strip_prefix(target).unwrap_or(path);
+        let folder = match relative.components().next() {
+            Some(first) if relative.components().count() > 1 => target.join(first),
+            _ => path.parent().unwrap_or(target).to_path_buf(),
+        };
+        match submissions.iter_mut().find(|(known, _)| *known == folder) {
+            Some((_, archives)) => archives.push((path, kind)),
+            None => submissions.push((folder, vec![(path, kind)])),
+        }
+    }
+    submissions
+}
+
+/// Removes everything below `dir` that isn't in `before`.
+fn remove_added(dir: &Path, before: &HashSet<PathBuf>) -> Result<()> {
+    let mut walkdir = WalkDir::new(dir).into_iter();
+    while let Some(entry) = walkdir.next() {
+        let Ok(entry) = entry else { continue };
+        if before.contains(entry.path()) {
+            continue;
+        }
+        if entry.file_type().is_dir() {
+            walkdir.skip_current_dir();
+        }
+        remove(entry.path(), false)?;
+    }
+    Ok(())
+}
+
+/// The changes [`unzip`] makes after extracting the outer archive with `files` (relative to
+/// `target`) as `(action, path, to)`, in the order they happen.
+fn unzip_plan(
+    files: &[PathBuf],
+    target: &Path,
+    single: bool,
+    flatten: bool,
+    ignored: &[String],
+) -> Vec<(&'static str, PathBuf, Option<PathBuf>)> {
+    let mut steps: Vec<_> = files
+        .iter()
+        .map(|file| ("Create", target.join(file), None))
+        .collect();
+    if single {
+        return steps;
+    }
+
+    // Paths relative to `target` that are gone once removed or flattened
+    let mut removed = HashSet::new();
+    let mut moved = HashSet::new();
+    for archive in files.iter().filter(|file| has_archive_extension(file)) {
+        let dir = archive.parent().unwrap_or(Path::new(""));
+        steps.push(("Extract", target.join(archive), Some(target.join(dir))));
+        let below = || files.iter().filter(|file| file.starts_with(dir));
+
+        for file in below() {
+            let mut path = dir.to_path_buf();
+            for part in file.strip_prefix(dir).unwrap_or(file).components() {
+                path.push(part);
+                let name = part.as_os_str().to_string_lossy().to_lowercase();
+                if ignored.iter().any(|ignored| name.contains(ignored)) {
+                    if removed.insert(path.clone()) {
+                        steps.push(("Delete", target.join(&path), None));
+                    }
+                    break;
+                }
+            }
+        }
+
+        if !flatten {
+            continue;
+        }
+        let mut subdirs = Vec::new();
+        for file in below() {
+            let relative = file.strip_prefix(dir).unwrap_or(file);
+            let Some(first) = relative.components().next() else {
+                continue;
+            };
+            if relative.components().count() < 2
+                || removed.iter().any(|path: &PathBuf| file.starts_with(path))
+                || !moved.insert(file.clone())
+            {
+                continue;
+            }
+            let to = target.join(dir).join(file.file_name().unwrap_or_default());
+            steps.push(("Move", target.join(file), Some(to)));
+            let subdir = dir.join(first);
+            if !subdirs.contains(&subdir) {
+                subdirs.push(subdir);
+            }
+        }
+        for subdir in subdirs {
+            if removed.insert(subdir.clone()) {
+                steps.push(("Delete", target.join(subdir), None));
+            }
+        }
+    }
+
+    steps
+}
+
+/// Extracts a submission archive and all archives nested inside into their parent directories.
+fn unzip_nested(
+    path: &Path,
+    kind: ArchiveKind,
+    target: &Path,
+    depth: usize,
+    limits: &Limits,
+    budget: &mut Budget,
+    debug: bool,
+) -> Result<()> {
+    if depth > limits.max_depth {
+        return Err(Error::LimitExceeded(format!(
+            "archives nested deeper than {} levels",
+            limits.max_depth
+        ))
+        .into());
+    }
+
+    let files = extract_reporting(path, kind, target, limits, budget, debug)?;
+    for (inner, kind) in archives(&files) {
+        let parent = inner.parent().unwrap_or(target);
+        unzip_nested(inner, kind, parent, depth + 1, limits, budget, debug)?;
+    }
+
+    Ok(())
+}
+
+/// Extracts an archive and warns about every entry that was skipped.
+fn extract_reporting(
+    path: &Path,
+    kind: ArchiveKind,
+    target: &Path,
+    limits: &Limits,
+    budget: &mut Budget,
+    debug: bool,
+) -> Result<Vec<PathBuf>> {
+    let extracted = extract(path, kind, target, limits, budget, debug)?;
+    for (entry, reason) in &extracted.rejected {
+        warn(
+            path.to_str().unwrap_or(""),
+            &format!("skipped entry `{}`: {}", entry, reason),
+        );
+    }
+    Ok(extracted.files)
+}
+
+fn archives(files: &[PathBuf]) -> impl Iterator<Item = (&PathBuf, ArchiveKind)> {
+    files
+        .iter()
+        .filter_map(|path| ArchiveKind::detect(path).map(|kind| (path, kind)))
+}
+
+/// What [`fill`] does with students that have no submission folder.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Unmatched {
+    /// Leave them out of the result
+    #[default]
+    Drop,
+    /// Copy their rows unchanged
+    Keep,
+    /// Grade them with 0 points and a comment that nothing was submitted
+    Zero,
+}
+
+impl std::str::FromStr for Unmatched {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "drop" => Ok(Unmatched::Drop),
+            "keep" => Ok(Unmatched::Keep),
+            "zero" => Ok(Unmatched::Zero),
+            _ => Err(format!("expected drop, keep or zero, got `{}`", s)),
+        }
+    }
+}
+
+/// How [`fill`] writes points and feedback into the table and how [`fill_table`] reads it.
+#[derive(Debug, Clone, Default)]
+pub struct FillOptions<'a> {
+    /// Text at the top of every feedback comment, the table locale's default if `None`
+    pub feedback: Option<&'a str>,
+    pub html: bool,
+    pub unmatched: Unmatched,
+    /// Deadline to report late submissions against and their penalty
+    pub late: Option<LatePolicy>,
+    /// Overwrite grades that are already in the table
+    pub force: bool,
+    /// CSV to write submission folders without a row in the table to
+    pub orphans: Option<&'a Path>,
+    /// Maximum points to grade those folders with, the most common in the table if `None`
+    pub max_points: Option<f32>,
+    /// Abort before writing anything if a row of the table can't be read
+    pub strict: bool,
+}
+
+/// Rows of the table that can't be read are copied to the result unchanged, or abort the
+/// run before anything is written in `strict` mode.
+///
+/// Submission folders without a row in the table are graded with `max_points`, or the
+/// maximum points most rows have, reported with their scores and written to `orphans` if given.
+pub fn fill_table(
+    table_path: &Path,
+    dir_path: &Path,
+    result_path: &Path,
+    grading: &Grading,
+    options: &FillOptions,
+    debug: bool,
+) -> Result<()> {
+    if !table_path.exists()
+        || !table_path.is_file()
+        || table_path.extension().is_none_or(|ext| ext != "csv")
+    {
+        return Err(Error::InvalidTable {
+            path: table_path.to_path_buf(),
+            reason: "expected a .csv file".to_string(),
+        }
+        .into());
+    }
+
+    dbglog!(
+        debug,
+        "Filling",
+        "table",
+        table_path.to_str().unwrap_or(""),
+        "submissions",
+        dir_path.to_str().unwrap_or("")
+    );
+    let dirs = get_dirs(dir_path)?;
+    let table = load_table(table_path, options.strict)?;
+    let mut problems = Problems::default();
+    let total = table.records.len();
+    let matched = table
+        .records
+        .iter()
+        .filter(|record| dirs.contains_key(&record.id))
+        .count();
+    let orphans = orphans(&table.records, &dirs);
+    let max_points = options
+        .max_points
+        .or_else(|| common_max_points(&table.records))
+        .unwrap_or(DEFAULT_MAX_POINTS as f32);
+    let graded: Vec<Graded> = orphans
+        .iter()
+        .filter_map(|dir| problems.check(grade(dir, max_points, grading)))
+        .collect();
+    let old: HashMap<String, Option<f32>> = table
+        .records
+        .iter()
+        .map(|record| (record.id.clone(), record.points))
+        .collect();
+    let records = fill(
+        table.records,
+        table.locale,
+        &dirs,
+        grading,
+        options,
+        &mut problems,
+    );
+
+    write_table(result_path, table.locale, &records, &table.rejected)?;
+    for record in &records {
+        let before = old.get(&record.id).copied().flatten();
+        if before != record.points {
+            let points = |points: Option<f32>| points.map_or("-".to_string(), |p| p.to_string());
+            println!(
+                "{:9}: {} ({}): {} -> {}",
+                "Changed",
+                record.name,
+                record.id,
+                points(before),
+                points(record.points)
+            );
+        }
+    }
+    for orphan in &graded {
+        warn(
+            &orphan.path.to_string_lossy(),
+            &format!(
+                "no row in the table, graded with {} of {} points",
+                orphan.points, max_points
+            ),
+        );
+    }
+    if let Some(orphans_path) = options.orphans {
+        write_orphans(orphans_path, &graded)?;
+    }
+    println!(
+        "{:9}: {} of {} students, {} without submission",
+        "Matched",
+        matched,
+        total,
+        total - matched
+    );
+
+    Ok(problems.finish()?)
+}
+
+/// The maximum points most of the `records` have, the first one if several are as common.
+fn common_max_points(records: &[Record]) -> Option<f32> {
+    let mut counts: Vec<(f32, usize)> = Vec::new();
+    for record in records {
+        match counts
+            .iter_mut()
+            .find(|(points, _)| *points == record.max_points)
+        {
+            Some((_, count)) => *count += 1,
+            None => counts.push((record.max_points, 1)),
+        }
+    }
+    // max_by_key keeps the last maximum
+    counts
+        .into_iter()
+        .rev()
+        .max_by_key(|(_, count)| *count)
+        .map(|(points, _)| points)
+}
+
+/// Submission folders in `dirs` whose ID has no record, sorted by path.
+pub fn orphans<'a>(records: &[Record], dirs: &'a HashMap<String, PathBuf>) -> Vec<&'a PathBuf> {
+    let ids: HashSet<&str> = records.iter().map(|record| record.id.as_str()).collect();
+    let mut orphans: Vec<_> = dirs
+        .iter()
+        .filter(|(id, _)| !ids.contains(id.as_str()))
+        .map(|(_, dir)| dir)
+        .collect();
+    orphans.sort();
+    orphans
+}
+
+/// Writes graded orphan folders as `Folder,ID,Points,Breakdown` for manual entry.
+fn write_orphans(path: &Path, orphans: &[Graded]) -> Result<()> {
+    let mut wtr = csv::Writer::from_path(path).map_err(|e| Error::InvalidTable {
+        path: path.to_path_buf(),
+        reason: e.to_string(),
+    })?;
+    wtr.write_record(["Folder", "ID", "Points", "Breakdown"])?;
+    for orphan in orphans {
+        let folder = orphan
+            .path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let id = moodle_id(&folder).unwrap_or_default().to_string();
+        wtr.write_record([
+            folder.clone(),
+            id,
+            orphan.points.to_string(),
+            orphan.tally.breakdown("; "),
+        ])?;
+    }
+    wtr.flush().map_err(io(path))?;
+    Ok(())
+}
+
+/// Reads a grading table and warns about every row that can't be read, which is an error
+/// in `strict` mode.
+fn load_table(table_path: &Path, strict: bool) -> Result<Table> {
+    let table = read_table(table_path)?;
+    let subject = table_path.to_string_lossy();
+    for row in &table.rejected {
+        warn(
+            &subject,
+            &format!("skipped line {}: {}", row.line, row.reason),
+        );
+    }
+
+    if strict && !table.rejected.is_empty() {
+        return Err(Error::InvalidTable {
+            path: table_path.to_path_buf(),
+            reason: format!("{} row(s) could not be read", table.rejected.len()),
+        }
+        .into());
+    }
+    Ok(table)
+}
+
+/// Grades the submission of every record that has one in `dirs` and writes the points and
+/// feedback comment into the record. Records without a submission are handled as
+/// `options.unmatched` says, records whose submission can't be graded are kept unchanged
+/// and the error is added to `problems`. Submissions after the deadline of `options.late` are reported and
+/// lose the penalty. Texts not given in `options` are taken from `locale`.
+///
+/// Rows that are locked in Moodle or, unless `options.force` is set, already graded are
+/// kept unchanged.
+pub fn fill(
+    records: Vec<Record>,
+    locale: &Locale,
+    dirs: &HashMap<String, PathBuf>,
+    grading: &Grading,
+    options: &FillOptions,
+    problems: &mut Problems,
+) -> Vec<Record> {
+    records
+        .into_iter()
+        .flat_map(|mut r| {
+            let d = match (dirs.get(&r.id), options.unmatched) {
+                (None, Unmatched::Drop) => return None,
+                (None, Unmatched::Keep) => return Some(r),
+                (_, _) if protected(&r, options.force) => return Some(r),
+                (Some(d), _) => d,
+                (None, Unmatched::Zero) => {
+                    r.points = Some(0.);
+                    r.feedback = match options.html {
+                        true => format!("<p>{}</p>", locale.no_submission),
+                        false => locale.no_submission.to_string(),
+                    };
+                    return Some(r);
+                }
+            };
+            let Some(graded) = problems.check(grade(d, r.max_points, grading)) else {
+                return Some(r);
+            };
+            if let Some(problem) = &graded.problem {
+                warn(d.to_str().unwrap_or(""), problem);
+            }
+            r.points = Some(graded.points);
+            r.feedback = tutors_feedback::comment(
+                &graded.deductions,
+                &graded.tally,
+                options.feedback.unwrap_or(locale.feedback),
+                options.html,
+            );
+            if let Some(policy) = &options.late {
+                apply_late(&mut r, d, policy, locale, options.html);
+            }
+            Some(r)
+        })
+        .collect()
+}
+
+/// Combines the filled tables of several tutors into one table at `result_path`.
+///
+/// Every student keeps the graded row of whichever table has one. Nothing is written if a
+/// student is graded differently in two tables.
+///
+/// With the `manifest` written by [`split`], students nobody graded and students graded by
+/// another tutor than the one they were assigned to are reported.
+pub fn merge_tables(
+    table_paths: &[PathBuf],
+    result_path: &Path,
+    manifest: Option<&Path>,
+    strict: bool,
+    debug: bool,
+) -> Result<()> {
+    let mut tables = Vec::new();
+    for path in table_paths {
+        dbglog!(debug, "Reading", "table", path.to_str().unwrap_or(""));
+        tables.push((path.as_path(), load_table(path, strict)?));
+    }
+    let Some((_, first)) = tables.first() else {
+        return Err(anyhow::anyhow!("No tables given"));
+    };
+    let locale = first.locale;
+
+    let mut problems = Problems::default();
+    let records = merge(&tables, &mut problems);
+    problems.finish()?;
+    if let Some(manifest) = manifest {
+        for (subject, message) in check_split(&read_manifest(manifest)?, &tables) {
+            warn(&subject, &message);
+        }
     }
 
-    let name_re = Regex::new(NAME_PATTERN)?;
+    let mut rejected: Vec<Rejected> = Vec::new();
+    for row in tables.into_iter().flat_map(|(_, table)| table.rejected) {
+        if !rejected.iter().any(|r| r.text == row.text) {
+            rejected.push(row);
+        }
+    }
+    write_table(result_path, locale, &records, &rejected)?;
+    println!(
+        "{:9}: {} students from {} tables, {} graded",
+        "Merged",
+        records.len(),
+        table_paths.len(),
+        records.iter().filter(|r| r.points.is_some()).count()
+    );
+
+    Ok(())
+}
+
+/// Merges the records of `tables` by ID in the order they first appear. A student graded in
+/// none of the tables keeps its first row, a student graded differently in two tables is
+/// added to `problems` and left out.
+///
+/// Zero points for a missing submission, as written by `fill` with [`Unmatched::Zero`], only
+/// count if no table has a real grade, as every tutor's table marks the other tutors'
+/// students that way.
+pub fn merge(tables: &[(&Path, Table)], problems: &mut Problems) -> Vec<Record> {
+    let mut ids: Vec<&str> = Vec::new();
+    let mut rows: HashMap<&str, Vec<(&Path, &Record, bool)>> = HashMap::new();
+    for (path, table) in tables {
+        for record in &table.records {
+            let entry = rows.entry(&record.id).or_default();
+            if entry.is_empty() {
+                ids.push(&record.id);
+            }
+            entry.push((path, record, no_submission(record, table.locale)));
+        }
+    }
+
+    ids.into_iter()
+        .filter_map(|id| {
+            let rows = &rows[id];
+            let graded: Vec<_> = rows
+                .iter()
+                .filter(|(_, r, missing)| r.points.is_some() && !missing)
+                .collect();
+            let Some((_, first, _)) = graded.first() else {
+                let row = rows.iter().find(|(_, _, missing)| *missing);
+                return Some(row.unwrap_or(&rows[0]).1.clone());
+            };
+            if graded
+                .iter()
+                .any(|(_, r, _)| r.points != first.points || r.feedback != first.feedback)
+            {
+                problems.push(Error::MergeConflict {
+                    id: id.to_string(),
+                    name: first.name.clone(),
+                    tables: graded
+                        .iter()
+                        .map(|(path, _, _)| path.to_path_buf())
+                        .collect(),
+                });
+                return None;
+            }
+            Some((*first).clone())
+        })
+        .collect()
+}
+
+/// Reads the `Tutor,Folder,ID` columns of a `split.csv` written by [`split`].
+fn read_manifest(path: &Path) -> Result<Vec<(String, String, String)>> {
+    let invalid = |reason: String| Error::InvalidTable {
+        path: path.to_path_buf(),
+        reason,
+    };
+    let mut rdr = csv::Reader::from_path(path).map_err(|e| invalid(e.to_string()))?;
+    let mut rows = Vec::new();
+    for row in rdr.records() {
+        let row = row.map_err(|e| invalid(e.to_string()))?;
+        match (row.get(0), row.get(1), row.get(2)) {
+            (Some(tutor), Some(folder), Some(id)) => {
+                rows.push((tutor.to_string(), folder.to_string(), id.to_string()))
+            }
+            _ => return Err(invalid("expected Tutor, Folder and ID columns".to_string()).into()),
+        }
+    }
+    Ok(rows)
+}
+
+/// Checks `tables` against the `manifest` of [`split`] and returns the findings as
+/// `(subject, message)`: students no table graded, and students graded in the table of
+/// another tutor. A table belongs to the tutor most of its graded students are assigned to.
+fn check_split(
+    manifest: &[(String, String, String)],
+    tables: &[(&Path, Table)],
+) -> Vec<(String, String)> {
+    fn graded(table: &Table) -> impl Iterator<Item = &Record> {
+        table
+            .records
+            .iter()
+            .filter(|r| r.points.is_some() && !no_submission(r, table.locale))
+    }
+
+    let tutors: HashMap<&str, &str> = manifest
+        .iter()
+        .map(|(tutor, _, id)| (id.as_str(), tutor.as_str()))
+        .collect();
+    let mut findings = Vec::new();
+    let mut covered = HashSet::new();
+    for (path, table) in tables {
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for record in graded(table) {
+            covered.insert(record.id.as_str());
+            if let Some(tutor) = tutors.get(record.id.as_str()) {
+                *counts.entry(tutor).or_default() += 1;
+            }
+        }
+        let owner = counts
+            .into_iter()
+            .max_by_key(|(tutor, count)| (*count, std::cmp::Reverse(*tutor)))
+            .map(|(tutor, _)| tutor);
+
+        for record in graded(table) {
+            let message = match (tutors.get(record.id.as_str()), owner) {
+                (None, _) => "is not in the split".to_string(),
+                (Some(tutor), Some(owner)) if *tutor != owner => {
+                    format!("is assigned to {}, not {}", tutor, owner)
+                }
+                _ => continue,
+            };
+            findings.push((
+                path.to_string_lossy().to_string(),
+                format!("{} ({}) {}", record.name, record.id, message),
+            ));
+        }
+    }
+    for (tutor, folder, id) in manifest {
+        if !covered.contains(id.as_str()) {
+            findings.push((tutor.clone(), format!("{} is not graded", folder)));
+        }
+    }
+    findings
+}
+
+/// Whether `record` got zero points from `fill` because there is no submission.
+fn no_submission(record: &Record, locale: &Locale) -> bool {
+    let feedback = record.feedback.trim_start_matches("<p>");
+    let feedback = feedback.trim_end_matches("</p>");
+    record.points == Some(0.) && feedback == locale.no_submission
+}
+
+/// Whether the grade of `record` must not change, with a warning if so.
+fn protected(record: &Record, force: bool) -> bool {
+    let subject = format!("{} ({})", record.name, record.id);
+    if record.is_locked() {
+        warn(&subject, "grade is locked in Moodle, not changed");
+        return true;
+    }
+    match (record.points, force) {
+        (Some(points), false) => {
+            warn(
+                &subject,
+                &format!("already graded with {} points, not changed", points),
+            );
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Reports a submission after the deadline and deducts the penalty from its points.
+fn apply_late(record: &mut Record, dir: &Path, policy: &LatePolicy, locale: &Locale, html: bool) {
+    let subject = dir.to_string_lossy();
+    let Some(submitted) = tutors_late::parse_date(&record.last_change_submission) else {
+        warn(
+            &subject,
+            &format!(
+                "can't read the submission date `{}`",
+                record.last_change_submission
+            ),
+        );
+        return;
+    };
+    let Some(minutes) = policy.lateness(submitted) else {
+        return;
+    };
+
+    let penalty = policy.penalty(minutes);
+    warn(
+        &subject,
+        &format!(
+            "submitted {} late, -{} points",
+            tutors_late::duration(minutes),
+            penalty
+        ),
+    );
+    if penalty > 0. {
+        record.points = record.points.map(|points| (points - penalty).max(0.));
+        record
+            .feedback
+            .push_str(&tutors_feedback::late(locale.late, minutes, penalty, html));
+    }
+}
+
+/// Prints a summary of the points in the filled table at `path`, or of the submission
+/// folders in `path` graded with `max_points` (25 by default).
+///
+/// A submission passes with at least `pass_ratio` of its maximum points.
+pub fn stats(
+    path: &Path,
+    max_points: &Option<u8>,
+    pass_ratio: f32,
+    grading: &Grading,
+    debug: bool,
+) -> Result<()> {
+    let mut scores = Vec::new();
+    let mut ungraded = 0;
+    let mut problems = Problems::default();
+
+    if path.is_file() {
+        if path.extension().is_none_or(|ext| ext != "csv") {
+            return Err(Error::InvalidTable {
+                path: path.to_path_buf(),
+                reason: "expected a .csv file".to_string(),
+            }
+            .into());
+        }
+        for record in load_table(path, false)?.records {
+            match record.points {
+                Some(points) => scores.push((points, record.max_points)),
+                None => ungraded += 1,
+            }
+        }
+    } else {
+        let max_points = max_points.unwrap_or(DEFAULT_MAX_POINTS) as f32;
+        let submissions = WalkDir::new(path)
+            .max_depth(1)
+            .into_iter()
+            .skip(1)
+            .flatten()
+            .filter(|entry| entry.path().is_dir());
+
+        for submission in submissions {
+            let points = match problems.check(grade(submission.path(), max_points, grading)) {
+                Some(graded) => graded.points,
+                None => continue,
+            };
+
+            dbglog!(
+                debug,
+                "Scored",
+                "path",
+                submission.path().to_str().unwrap_or(""),
+                "points",
+                &points.to_string()
+            );
+
+            scores.push((points, max_points));
+        }
+    }
+
+    match tutors_stats::summarize(&scores, ungraded, pass_ratio) {
+        Some(summary) => print!("{}", summary),
+        None => println!("No graded submissions found"),
+    }
+
+    Ok(problems.finish()?)
+}
+
+/// Writes `<name>.html`, and `<name>.pdf` if `pdf` is set, with a report of the tutor comments of each
+/// submission in `path` into the submission folder, so [`zipit`] packs it with the feedback.
+pub fn render(
+    path: &Path,
+    name: &str,
+    max_points: &Option<u8>,
+    grading: &Grading,
+    context: usize,
+    pdf: bool,
+    debug: bool,
+) -> Result<()> {
+    let max_points = max_points.unwrap_or(DEFAULT_MAX_POINTS) as f32;
+    let submissions = WalkDir::new(path)
+        .max_depth(1)
+        .into_iter()
+        .skip(1)
+        .flatten()
+        .filter(|entry| entry.path().is_dir());
+    let mut problems = Problems::default();
+
+    for submission in submissions {
+        let graded = match problems.check(grade(submission.path(), max_points, grading)) {
+            Some(graded) => graded,
+            None => continue,
+        };
+        let report = Report::new(&graded, max_points, context);
+
+        let html_path = submission.path().join(name).with_extension("html");
+        dbglog!(debug, "Render", "path", html_path.to_str().unwrap_or(""));
+        problems.check(std::fs::write(&html_path, report.html()).map_err(io(&html_path)));
+
+        if pdf {
+            let pdf_path = html_path.with_extension("pdf");
+            problems.check(std::fs::write(&pdf_path, report.pdf()).map_err(io(&pdf_path)));
+        }
+    }
+
+    Ok(problems.finish()?)
+}
+
+/// Packs the feedback of every submission in `path` into a zip named `name` and all of them
+/// into `feedbacks.zip` in `target_dir`, by default the parent of `path`.
+///
+/// Warns about folders Moodle would ignore on upload, see [`check_layout`].
+///
+/// The feedback files are deleted afterwards unless `keep` is set, in which case the
+/// feedback zips are only written into `feedbacks.zip`. Prints the files that would be
+/// created and deleted instead in `dry_run` mode.
+pub fn zipit(
+    name: String,
+    path: &Path,
+    target_dir: Option<&PathBuf>,
+    table_path: Option<&Path>,
+    keep: bool,
+    dry_run: bool,
+) -> Result<()> {
+    let ids = match table_path {
+        Some(table_path) => Some(
+            load_table(table_path, false)?
+                .records
+                .into_iter()
+                .map(|r| r.id)
+                .collect(),
+        ),
+        None => None,
+    };
+    for (folder, problem) in check_layout(path, ids.as_ref())? {
+        warn(&folder.to_string_lossy(), &problem);
+    }
+
+    let target_dir = match target_dir {
+        Some(path) => path,
+        None => path.parent().unwrap_or(Path::new(".")),
+    };
+    let outer_path = target_dir.join("feedbacks").with_extension("zip");
+
+    if keep {
+        if dry_run {
+            plan("Create", &outer_path, None);
+            return Ok(());
+        }
+        let outer_zip = File::create(&outer_path).map_err(io(&outer_path))?;
+        return zip_kept(&name, path, outer_zip);
+    }
+
+    let submissions = WalkDir::new(path).max_depth(1).into_iter().skip(1);
+    let mut problems = Problems::default();
+
+    for submission in submissions.flatten() {
+        problems.check(zip_submission(&name, submission.path(), dry_run));
+    }
+
+    if dry_run {
+        plan("Create", &outer_path, None);
+        return Ok(problems.finish()?);
+    }
+    let outer_zip = File::create(&outer_path).map_err(io(&outer_path))?;
+    zip_dir(outer_zip, path, |path| {
+        path.file_stem().is_some_and(|ext| ext.eq("feedbacks"))
+    })?;
+
+    Ok(problems.finish()?)
+}
+
+/// Imports the filled table at `table_path` into the gradebook at `book_path` as
+/// assignment `name`, which defaults to the file name of the table.
+pub fn gradebook_import(
+    book_path: &Path,
+    table_path: &Path,
+    name: Option<&str>,
+    debug: bool,
+) -> Result<()> {
+    let records = load_table(table_path, false)?.records;
+    let name = match name {
+        Some(name) => name.to_string(),
+        None => table_path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default(),
+    };
+
+    dbglog!(
+        debug,
+        "Importing",
+        "table",
+        table_path.to_str().unwrap_or(""),
+        "as",
+        &name
+    );
+    let mut gradebook = Gradebook::load(book_path)?;
+    let replaced = gradebook.import(&name, &records);
+    gradebook.save(book_path)?;
+    println!(
+        "{:9}: `{}`, {} of {} students graded",
+        if replaced { "Replaced" } else { "Imported" },
+        name,
+        records.iter().filter(|r| r.points.is_some()).count(),
+        records.len()
+    );
+    Ok(())
+}
+
+/// Prints the points of every student per assignment and in total.
+pub fn gradebook_show(book_path: &Path) -> Result<()> {
+    let gradebook = Gradebook::load(book_path)?;
+    let totals = gradebook.totals();
+    let width = totals
+        .iter()
+        .map(|t| t.name.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max(7);
+    let columns: Vec<_> = gradebook
+        .assignments
+        .iter()
+        .map(|a| a.name.chars().count().max(6))
+        .collect();
+
+    let mut header = format!("{:width$}", "Student");
+    for (assignment, column) in gradebook.assignments.iter().zip(&columns) {
+        header.push_str(&format!(" {:>column$}", assignment.name));
+    }
+    println!("{} {:>13}", header, "Total");
+    for total in &totals {
+        let mut line = format!("{:width$}", total.name);
+        for (assignment, column) in gradebook.assignments.iter().zip(&columns) {
+            match assignment.points.get(total.id) {
+                Some(points) => line.push_str(&format!(" {:>column$}", points)),
+                None => line.push_str(&format!(" {:>column$}", "-")),
+            }
+        }
+        let sum = format!("{}/{}", total.points, total.max_points);
+        println!("{} {:>13}", line, sum);
+    }
+    Ok(())
+}
+
+/// Prints every student without a grade per assignment.
+pub fn gradebook_missing(book_path: &Path) -> Result<()> {
+    let gradebook = Gradebook::load(book_path)?;
+    let missing = gradebook.missing();
+    for (assignment, id) in &missing {
+        println!(
+            "{:9}: {} ({})",
+            assignment, gradebook.students[*id].name, id
+        );
+    }
+    println!("{:9}: {} grade(s)", "Missing", missing.len());
+    Ok(())
+}
+
+/// Prints the final grade of every student from their total and `thresholds`, given as
+/// `(grade, lowest percentage)`.
+pub fn gradebook_grades(book_path: &Path, thresholds: &[(String, f32)]) -> Result<()> {
+    let gradebook = Gradebook::load(book_path)?;
+    let totals = gradebook.totals();
+    let width = totals
+        .iter()
+        .map(|t| t.name.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max(7);
+
+    println!(
+        "{:width$} {:>13} {:>7} {:>5}",
+        "Student", "Total", "Percent", "Grade"
+    );
+    for total in &totals {
+        let percent = total.percent();
+        println!(
+            "{:width$} {:>13} {:>6.1}% {:>5}",
+            total.name,
+            format!("{}/{}", total.points, total.max_points),
+            percent,
+            tutors_gradebook::grade(percent, thresholds).unwrap_or("-")
+        );
+    }
+    Ok(())
+}
+
+/// Distributes the submission folders in `path` among `tutors`, into a folder or zip per
+/// tutor in `target_dir`, and records the assignment in `split.csv` next to them. The filled
+/// tables of the tutors can be checked against it when they are merged, see [`merge_tables`].
+/// Entries that [`check_layout`] reports are left in place with a warning.
+pub fn split(
+    path: &Path,
+    tutors: &[String],
+    target_dir: Option<&PathBuf>,
+    options: &SplitOptions,
+    debug: bool,
+    dry_run: bool,
+) -> Result<()> {
+    if tutors.is_empty() {
+        return Err(anyhow::anyhow!("No tutors given"));
+    }
+    for (i, tutor) in tutors.iter().enumerate() {
+        // Tutor names become folder and file names in `target_dir`
+        if tutor.is_empty() || tutor == "." || tutor == ".." || tutor.contains(['/', '\\']) {
+            return Err(anyhow::anyhow!("Invalid tutor name `{}`", tutor));
+        }
+        if tutors[..i].contains(tutor) {
+            return Err(anyhow::anyhow!("Tutor `{}` given twice", tutor));
+        }
+    }
+    let canonical = path.canonicalize().map_err(io(path))?;
+    let target_dir = match target_dir {
+        Some(path) => path,
+        None => canonical.parent().unwrap_or(&canonical),
+    };
+    for (folder, problem) in check_layout(path, None)? {
+        warn(
+            &folder.to_string_lossy(),
+            &format!("not split, {}", problem),
+        );
+    }
 
-    let folders = WalkDir::new(path).max_depth(1).into_iter().skip(1);
-    let mut result = File::create(target_dir.join("result.csv"))?;
+    let mut folders: Vec<(String, PathBuf)> = get_dirs(path)?.into_iter().collect();
+    folders.sort_by(|a, b| a.1.cmp(&b.1));
+    let submissions: Vec<Submission> = folders
+        .iter()
+        .map(|(_, folder)| {
+            let name = folder.file_name().unwrap_or_default();
+            (name.to_string_lossy().to_string(), dir_size(folder))
+        })
+        .collect();
+    let assigned = assign(&submissions, tutors.len(), options.strategy, options.seed);
 
-    for folder in folders.flatten() {
-        let folder = folder.path();
-        let folder_name = match folder.file_stem() {
-            Some(name) => name.to_str().unwrap(),
-            None => continue,
-        };
-        let name = name_re
-            .captures(folder_name)
-            .unwrap()
-            .get(1)
-            .unwrap()
-            .as_str();
-
-        let file_walker = WalkDir::new(folder).into_iter();
-        let mut points = *max_points as f32;
-        for dir in file_walker.flatten() {
-            if dir
-                .path()
-                .extension()
-                .is_some_and(|ext| COUNTED_FILES.contains(&ext.to_str().unwrap()))
-            {
-                let file = File::open(dir.path())?;
-                let file = BufReader::new(file);
+    if !dry_run {
+        std::fs::create_dir_all(target_dir).map_err(io(target_dir))?;
+    }
+    let mut problems = Problems::default();
+    for (t, tutor) in tutors.iter().enumerate() {
+        let own: Vec<_> = folders
+            .iter()
+            .zip(&assigned)
+            .filter(|(_, &to)| to == t)
+            .map(|((_, folder), _)| folder.as_path())
+            .collect();
+
+        if options.zip {
+            let zip_path = target_dir.join(tutor).with_extension("zip");
+            match dry_run {
+                true => plan("Create", &zip_path, None),
+                false => {
+                    problems.check(zip_folders(&zip_path, path, &own));
+                }
+            }
+            continue;
+        }
 
-                points -= calculate_deduction(file)?;
+        let tutor_dir = target_dir.join(tutor);
+        if !dry_run {
+            std::fs::create_dir_all(&tutor_dir).map_err(io(&tutor_dir))?;
+        }
+        for folder in own {
+            let to = tutor_dir.join(folder.file_name().unwrap_or_default());
+            dbglog!(
+                debug,
+                "Split",
+                "from",
+                folder.to_str().unwrap_or(""),
+                "to",
+                to.to_str().unwrap_or("")
+            );
+            match (dry_run, options.copy) {
+                (true, true) => plan("Copy", folder, Some(&to)),
+                (true, false) => plan("Move", folder, Some(&to)),
+                (false, true) => {
+                    problems.check(copy_dir(folder, &to));
+                }
+                (false, false) => {
+                    problems.check(move_dir(folder, &to));
+                }
             }
         }
-        points = if points < 0. { 0. } else { points };
-        result.write_all(format!("{},{}\n", name, points).as_bytes())?;
     }
 
-    Ok(())
+    let manifest_path = target_dir.join("split.csv");
+    match dry_run {
+        true => plan("Create", &manifest_path, None),
+        false => write_manifest(&manifest_path, tutors, &folders, &submissions, &assigned)?,
+    }
+    for (t, tutor) in tutors.iter().enumerate() {
+        let (count, bytes) = submissions
+            .iter()
+            .zip(&assigned)
+            .filter(|(_, &to)| to == t)
+            .fold((0, 0), |(count, bytes), ((_, size), _)| {
+                (count + 1, bytes + size)
+            });
+        println!(
+            "{:9}: {} submissions, {} KiB",
+            tutor,
+            count,
+            bytes.div_ceil(1024)
+        );
+    }
+
+    Ok(problems.finish()?)
 }
 
-pub fn unzip(
-    path: &PathBuf,
-    single: bool,
-    flatten: bool,
-    target: Option<&PathBuf>,
-    debug: bool,
+/// Writes the assignment of `split` as `Tutor,Folder,ID,Bytes`.
+fn write_manifest(
+    path: &Path,
+    tutors: &[String],
+    folders: &[(String, PathBuf)],
+    submissions: &[Submission],
+    assigned: &[usize],
 ) -> Result<()> {
-    let file_name = Path::new(".").join(path.file_stem().unwrap());
-    let target = match target {
-        Some(path_buf) => path_buf.as_path(),
-        None => &file_name,
-    };
-
-    let mut archive = ZipArchive::new(File::open(path)?)?;
-    archive.extract(target)?;
-
-    // No more work to be done in single mode
-    if single {
-        //std::fs::remove_file(path)?;
-        return Ok(());
+    let mut wtr = csv::Writer::from_path(path).map_err(|e| Error::InvalidTable {
+        path: path.to_path_buf(),
+        reason: e.to_string(),
+    })?;
+    wtr.write_record(["Tutor", "Folder", "ID", "Bytes"])?;
+    for (((id, _), (name, bytes)), tutor) in folders.iter().zip(submissions).zip(assigned) {
+        wtr.write_record([&tutors[*tutor], name, id, &bytes.to_string()])?;
     }
+    wtr.flush().map_err(io(path))?;
+    Ok(())
+}
 
-    let walkdir = WalkDir::new(target).into_iter();
-
-    for entry in walkdir.flatten() {
-        if is_zip_file(&entry) {
-            let path = entry.path().to_path_buf();
-            let parent = path.parent().unwrap();
-            let target = &parent.to_path_buf();
-
-            dbglog!(debug, "Unzipping", "path", target.to_str().unwrap_or(""));
-
-            unzip(&path, true, flatten, Some(target), debug)?;
+/// Writes `folders` with everything below them into a new zip, with paths relative to `base`.
+fn zip_folders(zip_path: &Path, base: &Path, folders: &[&Path]) -> Result<()> {
+    let mut buffer = Vec::new();
+    let options = zip::write::FileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated)
+        .unix_permissions(0o755);
 
-            clean_dirs(target, debug)?;
+    let file = File::create(zip_path).map_err(io(zip_path))?;
+    let mut archive = zip::ZipWriter::new(file);
+    for folder in folders {
+        for entry in WalkDir::new(folder).into_iter().flatten() {
+            add_to_archive(&mut archive, entry.path(), base, &mut buffer, options)?;
+        }
+    }
+    archive.finish()?;
+    Ok(())
+}
 
-            if flatten {
-                flatten_dirs(target, None, debug)?;
+fn copy_dir(from: &Path, to: &Path) -> Result<()> {
+    for entry in WalkDir::new(from).into_iter().flatten() {
+        let target = to.join(entry.path().strip_prefix(from)?);
+        match entry.file_type().is_dir() {
+            true => std::fs::create_dir_all(&target).map_err(io(&target))?,
+            false => {
+                std::fs::copy(entry.path(), &target).map_err(io(entry.path()))?;
             }
         }
     }
-
     Ok(())
 }
 
-pub fn fill_table(
-    table_path: &Path,
-    dir_path: &Path,
-    result_path: &Path,
-    _debug: bool,
-) -> Result<()> {
-    if !table_path.exists()
-        || !table_path.is_file()
-        || !table_path.extension().is_some_and(|ext| ext == "csv")
-    {
-        return Err(anyhow::anyhow!("Table path not valid"));
+/// Renames `from` to `to`, or copies and removes it if they are on different file systems.
+fn move_dir(from: &Path, to: &Path) -> Result<()> {
+    match std::fs::rename(from, to) {
+        Err(err) if err.kind() == std::io::ErrorKind::CrossesDevices => {
+            copy_dir(from, to)?;
+            std::fs::remove_dir_all(from).map_err(io(from))?;
+            Ok(())
+        }
+        result => Ok(result.map_err(io(from))?),
     }
+}
 
-    let dirs = get_dirs(dir_path)?;
+/// Total size of the files below `dir` in bytes.
+fn dir_size(dir: &Path) -> u64 {
+    WalkDir::new(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.metadata().ok())
+        .filter(|metadata| metadata.is_file())
+        .map(|metadata| metadata.len())
+        .sum()
+}
 
-    let mut wtr = csv::WriterBuilder::new()
-        .delimiter(b',')
-        .from_path(result_path)?;
-
-    read_table(table_path)?
-        .iter_mut()
-        .flat_map(|record| dirs.get(&record.id).map(|dir| (record, dir)))
-        .map(|(r, d)| {
-            let deduction = sum_deduction(d).unwrap_or(0f32);
-            let points = 0f32.max(r.max_points - deduction);
-            r.points = Some(points);
-            r.feedback = FEEDBACK.to_string();
-            r
-        })
-        .for_each(|record| {
-            wtr.serialize(record).unwrap();
-        });
+/// The participant ID of a folder named like Moodle's `<name>_<id>_assignsubmission_file_`.
+pub fn moodle_id(folder_name: &str) -> Option<&str> {
+    let re = Regex::new(&format!(r"^.+_{}{}$", ID_PATTERN, SUBMISSION_SUFFIX)).ok()?;
+    re.captures(folder_name)?.get(1).map(|id| id.as_str())
+}
 
-    Ok(())
+/// The student name of a folder named like [`moodle_id`] expects.
+fn moodle_name(folder_name: &str) -> Option<&str> {
+    let id = moodle_id(folder_name)?;
+    let end = folder_name.len() - SUBMISSION_SUFFIX.len() - id.len() - 1;
+    Some(&folder_name[..end])
 }
 
-pub fn stats() -> Result<()> {
-    Err(anyhow::anyhow!("Not yet implemented"))
+/// Lists the entries of `path` whose feedback Moodle would drop on upload: anything that
+/// isn't a folder named like [`moodle_id`] expects and, if `ids` are given, folders of
+/// participants missing from the grading table.
+pub fn check_layout(path: &Path, ids: Option<&HashSet<String>>) -> Result<Vec<(PathBuf, String)>> {
+    let mut problems = Vec::new();
+    let entries = std::fs::read_dir(path).map_err(io(path))?;
+
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !entry.path().is_dir() {
+            problems.push((entry.path(), "not a submission folder".to_string()));
+            continue;
+        }
+        match (moodle_id(&name), ids) {
+            (None, _) => problems.push((
+                entry.path(),
+                format!("expected a folder named `<name>_<id>{}`", SUBMISSION_SUFFIX),
+            )),
+            (Some(id), Some(ids)) if !ids.contains(id) => problems.push((
+                entry.path(),
+                format!("ID {} is not in the grading table", id),
+            )),
+            _ => {}
+        }
+    }
+
+    problems.sort();
+    Ok(problems)
 }
 
-pub fn zipit(name: String, path: &Path, target_dir: Option<&PathBuf>) -> Result<()> {
-    let mut buffer = Vec::new();
+/// Writes the same layout as [`zipit`] into `writer`, building the feedback zips in memory
+/// so the submission folders are left untouched.
+#[allow(deprecated)]
+fn zip_kept<W: Write + Seek>(name: &str, path: &Path, writer: W) -> Result<()> {
     let options = zip::write::FileOptions::default()
         .compression_method(zip::CompressionMethod::Deflated)
         .unix_permissions(0o755);
+    let mut buffer = Vec::new();
+    let mut archive = zip::ZipWriter::new(writer);
+    let mut problems = Problems::default();
 
-    let submissions = WalkDir::new(path).max_depth(1).into_iter().skip(1);
+    let submissions = WalkDir::new(path)
+        .max_depth(1)
+        .into_iter()
+        .skip(1)
+        .flatten()
+        .filter(|entry| entry.path().is_dir());
+
+    for submission in submissions {
+        let submission = submission.path();
+        let inner_name = Path::new(name).with_extension("zip");
+        let is_zip = |path: &Path| path.extension().is_some_and(|ext| ext.eq("zip"));
+        let inner = match problems.check(zip_dir(Cursor::new(Vec::new()), submission, is_zip)) {
+            Some(inner) => inner.into_inner(),
+            None => continue,
+        };
 
-    for submission in submissions.flatten() {
-        let inner_path = submission.path().join(&name).with_extension("zip");
-        let inner_file = File::create(&inner_path)?;
-        let mut inner_archive = zip::ZipWriter::new(inner_file);
-        let feedback_files = WalkDir::new(submission.path())
+        let prefix = submission.strip_prefix(path)?;
+        archive.add_directory_from_path(prefix, options)?;
+        archive.start_file_from_path(&prefix.join(&inner_name), options)?;
+        archive.write_all(&inner)?;
+
+        // The original submission zip, but not a feedback zip left over from an earlier run
+        let originals = WalkDir::new(submission)
+            .max_depth(1)
             .into_iter()
             .skip(1)
             .flatten()
-            .filter(|entry| {
-                let path = entry.path();
-                !path.extension().is_some_and(|ext| ext.eq("zip"))
-            });
+            .filter(|entry| is_zip(entry.path()) && entry.file_name() != inner_name.as_os_str());
+        for original in originals {
+            add_to_archive(&mut archive, original.path(), path, &mut buffer, options)?;
+        }
+    }
 
-        // add files to feedback zip
-        for entry in feedback_files {
-            let path = entry.path();
-            let prefix = submission.path().to_str().unwrap();
+    archive.finish()?;
+    Ok(problems.finish()?)
+}
 
-            add_to_archive(&mut inner_archive, path, prefix, &mut buffer, options)?;
-        }
-        inner_archive.finish()?;
+/// Replaces the feedback files of a submission with a single zip named `name`.
+fn zip_submission(name: &str, submission: &Path, dry_run: bool) -> Result<()> {
+    let inner_path = submission.join(name).with_extension("zip");
+    if dry_run {
+        plan("Create", &inner_path, None);
+    } else {
+        let inner_file = File::create(&inner_path).map_err(io(&inner_path))?;
 
-        // remove feedback directory -> only feedback zip and original submission zip should be left
-        WalkDir::new(submission.path())
-            .into_iter()
-            .skip(1)
-            .flatten()
-            .filter(|entry| {
-                let path = entry.path();
-                !path.extension().is_some_and(|ext| ext == "zip")
-            })
-            .for_each(|entry| {
-                if entry.path().is_file() {
-                    std::fs::remove_file(entry.path()).unwrap();
-                } else {
-                    std::fs::remove_dir_all(entry.path()).unwrap();
-                }
-            });
+        // add files to feedback zip
+        zip_dir(inner_file, submission, |path| {
+            path.extension().is_some_and(|ext| ext.eq("zip"))
+        })?;
     }
 
-    let feedbacks = WalkDir::new(path)
+    // remove feedback directory -> only feedback zip and original submission zip should be left
+    let entries = WalkDir::new(submission)
+        .max_depth(1)
         .into_iter()
         .skip(1)
         .flatten()
-        .filter(|entry| {
-            let path = entry.path();
-            !path.file_stem().is_some_and(|ext| ext.eq("feedbacks"))
-        });
+        .filter(|entry| entry.path().extension().is_none_or(|ext| ext != "zip"));
+    for entry in entries {
+        remove(entry.path(), dry_run)?;
+    }
 
-    let target_dir = match target_dir {
-        Some(path) => path,
-        None => path.parent().unwrap(),
-    };
+    Ok(())
+}
 
-    let outer_zip = File::create(target_dir.join("feedbacks").with_extension("zip"))?;
-    let mut outer_archive = zip::ZipWriter::new(outer_zip);
+/// Writes everything below `dir` into a new zip archive, with paths relative to `dir`.
+/// Files and directories for which `skip` returns true are left out.
+pub fn zip_dir<W: Write + Seek>(writer: W, dir: &Path, skip: impl Fn(&Path) -> bool) -> Result<W> {
+    let mut buffer = Vec::new();
+    let options = zip::write::FileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated)
+        .unix_permissions(0o755);
 
-    for entry in feedbacks {
-        let prefix = path.to_str().unwrap();
-        let path = entry.path();
+    let mut archive = zip::ZipWriter::new(writer);
+    let entries = WalkDir::new(dir)
+        .into_iter()
+        .skip(1)
+        .flatten()
+        .filter(|entry| !skip(entry.path()));
 
-        add_to_archive(&mut outer_archive, path, prefix, &mut buffer, options)?;
+    for entry in entries {
+        add_to_archive(&mut archive, entry.path(), dir, &mut buffer, options)?;
     }
-    outer_archive.finish()?;
-    Ok(())
+
+    Ok(archive.finish()?)
 }
 
 #[allow(deprecated)]
-fn add_to_archive(
-    archive: &mut zip::ZipWriter<File>,
+fn add_to_archive<W: Write + Seek>(
+    archive: &mut zip::ZipWriter<W>,
     path: &Path,
-    prefix: &str,
+    prefix: &Path,
     buffer: &mut Vec<u8>,
     options: zip::write::FileOptions,
 ) -> Result<()> {
-    let name = path.strip_prefix(prefix).unwrap();
+    let name = path.strip_prefix(prefix)?;
 
     if path.is_file() {
         archive.start_file_from_path(name, options)?;
-        let mut file = File::open(path)?;
-        file.read_to_end(buffer)?;
+        let mut file = File::open(path).map_err(io(path))?;
+        file.read_to_end(buffer).map_err(io(path))?;
         archive.write_all(buffer)?;
         buffer.clear();
     } else {
@@ -254,34 +1521,19 @@ fn add_to_archive(
     Ok(())
 }
 
-fn calculate_deduction(file: BufReader<File>) -> Result<f32> {
-    let tut_re = Regex::new(TUTOR_PATTERN)?;
-    let mut result = 0f32;
-
-    for line in file.lines().map_while(Result::ok) {
-        tut_re.captures_iter(&line).for_each(|cap| {
-            if let Some(deduction) = cap.get(2) {
-                let deduction = deduction.as_str().parse::<f32>().unwrap_or(0f32);
-                result += deduction;
-            }
-        });
-    }
-
-    Ok(result)
-}
+fn clean_dirs(path: &Path, ignored: &[String], debug: bool, dry_run: bool) -> Result<()> {
+    let mut walkdir = WalkDir::new(path).into_iter();
+    while let Some(entry) = walkdir.next() {
+        let Ok(entry) = entry else { continue };
+        let file_name = entry.file_name().to_string_lossy().to_lowercase();
+        if !ignored.iter().any(|name| file_name.contains(name)) {
+            continue;
+        }
+        // Everything below is removed together with the directory
+        if entry.file_type().is_dir() {
+            walkdir.skip_current_dir();
+        }
 
-fn clean_dirs(path: &Path, debug: bool) -> Result<()> {
-    let walkdir = WalkDir::new(path).into_iter().flatten().filter(|entry| {
-        IGNORED_NAMES.iter().any(|name| {
-            entry
-                .file_name()
-                .to_str()
-                .unwrap()
-                .to_lowercase()
-                .contains(name)
-        })
-    });
-    for entry in walkdir {
         dbglog!(
             debug,
             "Removing",
@@ -289,17 +1541,31 @@ fn clean_dirs(path: &Path, debug: bool) -> Result<()> {
             entry.path().to_str().unwrap_or("")
         );
 
-        if entry.path().is_file() {
-            std::fs::remove_file(entry.path())?;
-        } else {
-            std::fs::remove_dir_all(entry.path())?;
-        }
+        remove(entry.path(), dry_run)?;
     }
 
     Ok(())
 }
 
-fn flatten_dirs(path: &Path, to: Option<&Path>, debug: bool) -> Result<()> {
+fn remove(path: &Path, dry_run: bool) -> Result<(), Error> {
+    if dry_run {
+        plan("Delete", path, None);
+        return Ok(());
+    }
+    match path.is_file() {
+        true => std::fs::remove_file(path),
+        false => std::fs::remove_dir_all(path),
+    }
+    .map_err(io(path))
+}
+
+fn flatten_dirs(
+    path: &Path,
+    to: Option<&Path>,
+    ignored: &[String],
+    debug: bool,
+    dry_run: bool,
+) -> Result<()> {
     let walkdir = WalkDir::new(path)
         .max_depth(1)
         .into_iter()
@@ -316,22 +1582,14 @@ fn flatten_dirs(path: &Path, to: Option<&Path>, debug: bool) -> Result<()> {
         );
 
         let to = to.unwrap_or_else(|| entry.path().parent().unwrap_or(Path::new("/")));
-        flatten_dirs(entry.path(), Some(to), debug)?;
-        move_files(entry.path(), to, debug)?;
-        std::fs::remove_dir_all(entry.path())?;
+        flatten_dirs(entry.path(), Some(to), ignored, debug, dry_run)?;
+        move_files(entry.path(), to, ignored, debug, dry_run)?;
+        remove(entry.path(), dry_run)?;
     }
 
     Ok(())
 }
 
-fn is_zip_file(entry: &DirEntry) -> bool {
-    entry
-        .file_name()
-        .to_str()
-        .map(|s| s.ends_with(".zip"))
-        .unwrap_or(false)
-}
-
 fn log(command: &str, args: Vec<(&str, &str)>) {
     let mut prefix = "";
     let args = args.iter().fold(String::new(), |mut acc, (key, value)| {
@@ -342,79 +1600,128 @@ fn log(command: &str, args: Vec<(&str, &str)>) {
     println!("{:9}: {}", command, args);
 }
 
-fn move_files(path: &Path, to: &Path, debug: bool) -> Result<()> {
-    WalkDir::new(path)
+fn warn(subject: &str, message: &str) {
+    eprintln!("{:9}: {}: {}", "Warning", subject, message);
+}
+
+/// Prints a filesystem change that is skipped in dry-run mode.
+fn plan(action: &str, path: &Path, to: Option<&Path>) {
+    match to {
+        Some(to) => println!("{:9}: {} -> {}", action, path.display(), to.display()),
+        None => println!("{:9}: {}", action, path.display()),
+    }
+}
+
+fn move_files(
+    path: &Path,
+    to: &Path,
+    ignored: &[String],
+    debug: bool,
+    dry_run: bool,
+) -> Result<()> {
+    let files = WalkDir::new(path)
         .max_depth(1)
         .into_iter()
         .flatten()
         .filter(|entry| entry.path().is_file())
-        .filter(not_ignored)
-        .for_each(|entry| {
-            let from = entry.path();
-            std::fs::copy(from, to.join(entry.file_name())).unwrap();
+        .filter(|entry| not_ignored(entry, ignored));
 
-            dbglog!(
-                debug,
-                "Moving",
-                "from",
-                from.to_str().unwrap_or(""),
-                "to",
-                to.to_str().unwrap_or("")
-            );
-        });
+    for entry in files {
+        let from = entry.path();
+        if dry_run {
+            plan("Move", from, Some(&to.join(entry.file_name())));
+            continue;
+        }
+        std::fs::copy(from, to.join(entry.file_name())).map_err(io(from))?;
+
+        dbglog!(
+            debug,
+            "Moving",
+            "from",
+            from.to_str().unwrap_or(""),
+            "to",
+            to.to_str().unwrap_or("")
+        );
+    }
     Ok(())
 }
 
-fn not_ignored(entry: &DirEntry) -> bool {
+fn not_ignored(entry: &DirEntry, ignored: &[String]) -> bool {
     entry
         .file_name()
         .to_str()
-        .map(|s| {
-            !IGNORED_NAMES
-                .iter()
-                .any(|name| s.to_lowercase().contains(name))
-        })
+        .map(|s| !ignored.iter().any(|name| s.to_lowercase().contains(name)))
         .unwrap_or(false)
 }
 
-fn read_table(table_path: &Path) -> Result<Vec<Record>> {
-    let mut reader = csv::ReaderBuilder::new()
-        .delimiter(b',')
-        .has_headers(true)
-        .from_path(table_path)?;
+/// A graded submission folder.
+#[derive(Debug)]
+pub struct Graded {
+    pub path: PathBuf,
+    pub deductions: Vec<Deduction>,
+    pub tally: Tally,
+    pub points: f32,
+    /// Set when the tutor comments don't add up, see [`Grading::check`]
+    pub problem: Option<String>,
+}
 
-    let vec = reader
-        .deserialize::<Record>()
-        .filter_map(Result::ok)
-        .collect();
+/// Grades the submission in `dir`, which is worth `max_points`.
+pub fn grade(dir: &Path, max_points: f32, grading: &Grading) -> Result<Graded> {
+    let deductions = sum_deduction(dir, &grading.languages)?;
+    Ok(Graded::new(dir, deductions, max_points, grading))
+}
 
-    Ok(vec)
+impl Graded {
+    pub fn new(
+        path: &Path,
+        deductions: Vec<Deduction>,
+        max_points: f32,
+        grading: &Grading,
+    ) -> Self {
+        let tally = tally(&deductions, &grading.limits);
+
+        Graded {
+            path: path.to_path_buf(),
+            points: grading.score(max_points, &tally),
+            problem: grading.check(max_points, &tally),
+            deductions,
+            tally,
+        }
+    }
 }
 
-fn sum_deduction(dir_path: &Path) -> Result<f32> {
+/// Collects the tutor comments of all files below `dir_path` written in one of `languages`.
+pub fn sum_deduction(dir_path: &Path, languages: &Languages) -> Result<Vec<Deduction>> {
     let file_walker = WalkDir::new(dir_path)
         .into_iter()
         .flatten()
         .map(|entry| entry.path().to_path_buf())
-        .filter(|path| {
-            path.is_file()
-                && path
-                    .extension()
-                    .is_some_and(|ext| COUNTED_FILES.contains(&ext.to_str().unwrap_or_default()))
-        });
-
-    let mut deduction = 0f32;
-    for file in file_walker {
-        let file = File::open(file)?;
+        .filter(|path| path.is_file());
+
+    let mut deductions = Vec::new();
+    for path in file_walker {
+        let patterns = match path
+            .extension()
+            .and_then(|ext| languages.patterns(ext.to_str().unwrap_or_default()))
+        {
+            Some(patterns) => patterns,
+            None => continue,
+        };
+        let file = File::open(&path).map_err(io(&path))?;
         let file = BufReader::new(file);
+        let source = path.strip_prefix(dir_path).unwrap_or(&path);
 
-        deduction += calculate_deduction(file)?;
+        deductions.extend(
+            calculate_deduction(source, file, patterns)
+                .with_context(|| dir_path.display().to_string())?,
+        );
     }
 
-    Ok(deduction)
+    Ok(deductions)
 }
-fn get_dirs(dir_path: &Path) -> Result<HashMap<String, PathBuf>> {
-    let re = Regex::new(ID_PATTERN)?;
+
+/// Maps the participant ID in each submission folder name inside `dir_path` to the folder.
+pub fn get_dirs(dir_path: &Path) -> Result<HashMap<String, PathBuf>> {
     let walkdir = WalkDir::new(dir_path).max_depth(1).into_iter();
 
     let map: HashMap<_, _> = walkdir
@@ -422,10 +1729,8 @@ fn get_dirs(dir_path: &Path) -> Result<HashMap<String, PathBuf>> {
         .flatten()
         .filter(|entry| entry.path().is_dir())
         .flat_map(|entry| {
-            let entry = entry.path();
-            let name = entry.to_str().unwrap();
-            let cap = re.captures(name).unwrap().get(1);
-            cap.map(|cap| (cap.as_str().to_string(), entry.to_path_buf()))
+            let id = moodle_id(&entry.file_name().to_string_lossy())?.to_string();
+            Some((id, entry.path().to_path_buf()))
         })
         .collect();
 
@@ -434,21 +1739,751 @@ fn get_dirs(dir_path: &Path) -> Result<HashMap<String, PathBuf>> {
 
 #[cfg(test)]
 mod tests {
+    use std::fs::create_dir_all;
+
     use super::*;
+    use crate::tutors_csv::{test_table, LOCALES};
 
+    #[test]
     fn test_count() -> Result<()> {
-        todo!()
+        let tmp = tempfile::tempdir()?;
+        let dir = tmp.path().to_path_buf();
+        let submissions = dir.join("submissions");
+        let submission = submissions.join("Max_123_assignsubmission_file_");
+        create_dir_all(&submission)?;
+        std::fs::write(submission.join("Main.java"), "// Tutor[A1]: -2 wrong\n")?;
+        create_dir_all(submissions.join("Doe, Jo_456_assignsubmission_file_"))?;
+        std::fs::write(submissions.join("notes.txt"), "")?;
+
+        let target = dir.join("out");
+        count(&submissions, &target, &Some(10), &Grading::default(), false)?;
+        assert_eq!(
+            moodle_name("Doe, Jo_456_assignsubmission_file_"),
+            Some("Doe, Jo")
+        );
+
+        let result = std::fs::read_to_string(target.join("result.csv"))?;
+        let mut lines: Vec<_> = result.lines().collect();
+        lines.sort();
+        assert_eq!(lines, ["\"Doe, Jo\",10,", "Max,8,A1: -2"]);
+
+        create_dir_all(submissions.join("Eva_456"))?;
+        let err = count(&submissions, &target, &Some(10), &Grading::default(), false).unwrap_err();
+        assert!(err.to_string().contains("Eva_456"));
+        Ok(())
     }
+
+    #[test]
     fn test_unzip() -> Result<()> {
-        todo!()
+        let tmp = tempfile::tempdir()?;
+        let dir = tmp.path().to_path_buf();
+
+        let zip_path = dir.join("submissions.zip");
+        let mut zip = zip::ZipWriter::new(File::create(&zip_path)?);
+        let options = zip::write::FileOptions::default();
+        zip.start_file("Max_123_assignsubmission_file_/Main.java", options)?;
+        zip.write_all(b"class Main {}")?;
+        zip.start_file("../evil.txt", options)?;
+        zip.write_all(b"evil")?;
+        let absolute = dir.join("abs.txt");
+        zip.start_file(absolute.to_string_lossy(), options)?;
+        zip.write_all(b"evil")?;
+        zip.add_symlink(
+            "Max_123_assignsubmission_file_/link",
+            "/etc/passwd",
+            options,
+        )?;
+        zip.finish()?;
+
+        let target = dir.join("out");
+        unzip(
+            &zip_path,
+            true,
+            false,
+            Some(&target),
+            &[],
+            &Limits::default(),
+            false,
+            false,
+        )?;
+
+        let submission = target.join("Max_123_assignsubmission_file_");
+        assert!(submission.join("Main.java").is_file());
+        assert!(!submission.join("link").exists());
+        assert!(!dir.join("evil.txt").exists());
+        assert!(!absolute.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_unzip_budget() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let dir = tmp.path().to_path_buf();
+        let inner = |name: &str| -> Result<Vec<u8>> {
+            let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+            zip.start_file(name, zip::write::FileOptions::default())?;
+            zip.write_all(&[b'x'; 400])?;
+            Ok(zip.finish()?.into_inner())
+        };
+
+        let zip_path = dir.join("submissions.zip");
+        let mut zip = zip::ZipWriter::new(File::create(&zip_path)?);
+        let options = zip::write::FileOptions::default();
+        for (folder, archives) in [("Max_1", 3), ("Eva_2", 2)] {
+            for i in 0..archives {
+                let name = format!("{}_assignsubmission_file_/part{}.zip", folder, i);
+                zip.start_file(name, options)?;
+                zip.write_all(&inner(&format!("src/Part{}.java", i))?)?;
+            }
+        }
+        zip.finish()?;
+
+        let target = dir.join("out");
+        let limits = Limits {
+            max_bytes: 1000,
+            ..Limits::default()
+        };
+        unzip(
+            &zip_path,
+            false,
+            false,
+            Some(&target),
+            &[],
+            &limits,
+            false,
+            false,
+        )?;
+
+        // Each archive fits, all three of Max's don't
+        let max = target.join("Max_1_assignsubmission_file_");
+        assert!(!max.join("src").exists());
+        assert!(max.join("part2.zip").is_file());
+        let eva = target.join("Eva_2_assignsubmission_file_");
+        assert!(eva.join("src/Part0.java").is_file());
+        assert!(eva.join("src/Part1.java").is_file());
+        Ok(())
+    }
+
+    #[test]
+    fn test_unzip_dry_run() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let dir = tmp.path().to_path_buf();
+
+        let zip_path = dir.join("submissions.zip");
+        let mut zip = zip::ZipWriter::new(File::create(&zip_path)?);
+        let options = zip::write::FileOptions::default();
+        for name in [
+            "Max_123_assignsubmission_file_/Abgabe.zip",
+            "Max_123_assignsubmission_file_/src/Main.java",
+            "Max_123_assignsubmission_file_/src/.git/config",
+        ] {
+            zip.start_file(name, options)?;
+            zip.write_all(b"x")?;
+        }
+        zip.finish()?;
+
+        let target = dir.join("out");
+        let ignored = [".git".to_string()];
+        unzip(
+            &zip_path,
+            false,
+            true,
+            Some(&target),
+            &ignored,
+            &Limits::default(),
+            false,
+            true,
+        )?;
+        assert!(!target.exists());
+
+        let files = list(&zip_path, ArchiveKind::Zip)?;
+        let submission = target.join("Max_123_assignsubmission_file_");
+        let steps = unzip_plan(&files, &target, false, true, &ignored);
+        let actions: Vec<_> = steps.iter().map(|(action, _, _)| *action).collect();
+        assert_eq!(
+            actions,
+            ["Create", "Create", "Create", "Extract", "Delete", "Move", "Delete"]
+        );
+        assert_eq!(steps[4].1, submission.join("src/.git"));
+        assert_eq!(
+            steps[5],
+            (
+                "Move",
+                submission.join("src/Main.java"),
+                Some(submission.join("Main.java"))
+            )
+        );
+        assert_eq!(steps[6].1, submission.join("src"));
+
+        let steps = unzip_plan(&files, &target, true, true, &ignored);
+        assert_eq!(steps.len(), 3);
+        Ok(())
     }
+    #[test]
     fn test_fill_table() -> Result<()> {
-        todo!()
+        let tmp = tempfile::tempdir()?;
+        let dir = tmp.path().to_path_buf();
+        let submission = dir.join("submissions/Max_1_assignsubmission_file_");
+        create_dir_all(&submission)?;
+        std::fs::write(submission.join("Main.java"), "// Tutor: -3\n")?;
+        let orphan = dir.join("submissions/Eva_2_assignsubmission_file_");
+        create_dir_all(&orphan)?;
+        std::fs::write(orphan.join("Main.java"), "// Tutor: -1\n")?;
+        let table_path = dir.join("table.csv");
+        std::fs::write(
+            &table_path,
+            format!(
+                "{}\nTeilnehmer/in1,Max,,,,,\"10,00\",Ja,,,\n",
+                LOCALES[0].headers.join(",")
+            ),
+        )?;
+        let options = FillOptions {
+            feedback: None,
+            html: false,
+            unmatched: Unmatched::Keep,
+            late: None,
+            force: false,
+            ..Default::default()
+        };
+
+        let result_path = dir.join("result.csv");
+        fill_table(
+            &table_path,
+            &dir.join("submissions"),
+            &result_path,
+            &Grading::default(),
+            &options,
+            false,
+        )?;
+
+        let table = read_table(&result_path)?;
+        assert_eq!(table.records[0].points, Some(7.));
+        assert!(table.records[0].feedback.starts_with(LOCALES[0].feedback));
+
+        // Graded orphans are written when asked for
+        let orphans_path = dir.join("orphans.csv");
+        fill_table(
+            &table_path,
+            &dir.join("submissions"),
+            &result_path,
+            &Grading::default(),
+            &FillOptions {
+                orphans: Some(&orphans_path),
+                ..options.clone()
+            },
+            false,
+        )?;
+        let orphans = std::fs::read_to_string(&orphans_path)?;
+        assert_eq!(
+            orphans.lines().nth(1),
+            Some("Eva_2_assignsubmission_file_,2,9,")
+        );
+        assert!(fill_table(
+            &dir,
+            &dir,
+            &result_path,
+            &Grading::default(),
+            &options,
+            false
+        )
+        .is_err());
+        Ok(())
+    }
+    #[test]
+    fn test_grade() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let dir = tmp.path().to_path_buf();
+        let submission = dir.join("Max_123_assignsubmission_file_");
+        create_dir_all(submission.join("src"))?;
+        std::fs::write(submission.join("src/Main.java"), "// Tutor[A1]: -2 wrong\n")?;
+        std::fs::write(submission.join("notes.txt"), "// Tutor: -5\n")?;
+
+        let dirs = get_dirs(&dir)?;
+        assert_eq!(dirs["123"], submission);
+
+        let graded = grade(&submission, 10., &Grading::default())?;
+        assert_eq!(graded.points, 8.);
+        assert_eq!(graded.deductions[0].file, Path::new("src/Main.java"));
+
+        let zipped = zip_dir(std::io::Cursor::new(Vec::new()), &dir, |_| false)?;
+        let archive = zip::ZipArchive::new(zipped)?;
+        assert!(archive
+            .file_names()
+            .any(|name| name == "Max_123_assignsubmission_file_/src/Main.java"));
+
+        // Errors name the submission, not only the file inside it
+        std::fs::write(submission.join("src/Util.java"), "// Tutor: good job\n")?;
+        let err = grade(&submission, 10., &Grading::default()).unwrap_err();
+        assert_eq!(
+            format!("{:#}", err),
+            format!(
+                "{}: src/Util.java:1: tutor comment without points",
+                submission.display()
+            )
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_dry_run() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let dir = tmp.path().to_path_buf();
+        let submission = dir.join("Max_123_assignsubmission_file_");
+        create_dir_all(submission.join("src/.git"))?;
+        std::fs::write(submission.join("src/Main.java"), "class Main {}")?;
+
+        let ignored = vec![".git".to_string()];
+        clean_dirs(&dir, &ignored, false, true)?;
+        flatten_dirs(&dir, None, &ignored, false, true)?;
+        zipit("feedback".to_string(), &dir, Some(&dir), None, false, true)?;
+
+        assert!(submission.join("src/.git").is_dir());
+        assert!(submission.join("src/Main.java").is_file());
+        assert!(!submission.join("feedback.zip").exists());
+        assert!(!dir.join("feedbacks.zip").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_zipit_keep() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let dir = tmp.path().to_path_buf();
+        let submissions = dir.join("submissions");
+        let submission = submissions.join("Max_123_assignsubmission_file_");
+        create_dir_all(submission.join("src"))?;
+        std::fs::write(submission.join("src/Main.java"), "// Tutor: -1")?;
+        std::fs::write(submission.join("original.zip"), "")?;
+
+        zipit(
+            "feedback".to_string(),
+            &submissions,
+            None,
+            None,
+            true,
+            false,
+        )?;
+
+        assert!(submission.join("src/Main.java").is_file());
+        assert!(!submission.join("feedback.zip").exists());
+
+        let mut outer = zip::ZipArchive::new(File::open(dir.join("feedbacks.zip"))?)?;
+        let mut names: Vec<_> = outer.file_names().map(str::to_string).collect();
+        names.sort();
+        assert_eq!(
+            names,
+            [
+                "Max_123_assignsubmission_file_/",
+                "Max_123_assignsubmission_file_/feedback.zip",
+                "Max_123_assignsubmission_file_/original.zip",
+            ]
+        );
+
+        let mut inner = Vec::new();
+        outer
+            .by_name("Max_123_assignsubmission_file_/feedback.zip")?
+            .read_to_end(&mut inner)?;
+        let inner = zip::ZipArchive::new(Cursor::new(inner))?;
+        assert!(inner.file_names().any(|name| name == "src/Main.java"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_layout() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let dir = tmp.path().to_path_buf();
+        create_dir_all(dir.join("Max Muster_123_assignsubmission_file_"))?;
+        create_dir_all(dir.join("Eva_456_assignsubmission_file_"))?;
+        create_dir_all(dir.join("Eva_456"))?;
+        std::fs::write(dir.join("notes.txt"), "")?;
+
+        assert_eq!(
+            moodle_id("Max Muster_123_assignsubmission_file_"),
+            Some("123")
+        );
+        assert_eq!(moodle_id("Max_123_assignsubmission_file_copy"), None);
+
+        let ids = HashSet::from(["123".to_string()]);
+        let problems: Vec<_> = check_layout(&dir, Some(&ids))?
+            .into_iter()
+            .map(|(path, _)| path.file_name().unwrap().to_owned())
+            .collect();
+        assert_eq!(
+            problems,
+            ["Eva_456", "Eva_456_assignsubmission_file_", "notes.txt"]
+        );
+        assert_eq!(check_layout(&dir, None)?.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_orphans() {
+        let records = test_table("Teilnehmer/in1,Max,,,,,\"10,00\",Ja,,,\n").records;
+        let dirs = HashMap::from([
+            (
+                "1".to_string(),
+                PathBuf::from("Max_1_assignsubmission_file_"),
+            ),
+            (
+                "3".to_string(),
+                PathBuf::from("Tim_3_assignsubmission_file_"),
+            ),
+            (
+                "2".to_string(),
+                PathBuf::from("Eva_2_assignsubmission_file_"),
+            ),
+        ]);
+
+        assert_eq!(
+            orphans(&records, &dirs),
+            [
+                Path::new("Eva_2_assignsubmission_file_"),
+                Path::new("Tim_3_assignsubmission_file_")
+            ]
+        );
+
+        let records = test_table(
+            "Teilnehmer/in1,Max,,,,,\"10,00\",Ja,,,\n\
+             Teilnehmer/in2,Eva,,,,,\"20,00\",Ja,,,\n\
+             Teilnehmer/in3,Tim,,,,,\"20,00\",Ja,,,\n",
+        )
+        .records;
+        assert_eq!(common_max_points(&records), Some(20.));
+        assert_eq!(common_max_points(&records[..2]), Some(10.));
+        assert_eq!(common_max_points(&[]), None);
+    }
+
+    #[test]
+    fn test_merge() {
+        let first = test_table(
+            "Teilnehmer/in1,Max,,,,\"8,00\",\"10,00\",Ja,,,gut\n\
+             Teilnehmer/in2,Eva,,,,,\"10,00\",Ja,,,\n\
+             Teilnehmer/in3,Tim,,,,\"5,00\",\"10,00\",Ja,,,ok\n\
+             Teilnehmer/in5,Ute,,,,\"0,00\",\"10,00\",Ja,,,Keine Abgabe.\n\
+             Teilnehmer/in6,Jan,,,,\"0,00\",\"10,00\",Ja,,,<p>Keine Abgabe.</p>\n",
+        );
+        let second = test_table(
+            "Teilnehmer/in2,Eva,,,,\"9,00\",\"10,00\",Ja,,,fein\n\
+             Teilnehmer/in3,Tim,,,,\"6,00\",\"10,00\",Ja,,,ok\n\
+             Teilnehmer/in4,Ida,,,,,\"10,00\",Ja,,,\n\
+             Teilnehmer/in5,Ute,,,,\"7,00\",\"10,00\",Ja,,,gut\n\
+             Teilnehmer/in6,Jan,,,,,\"10,00\",Ja,,,\n",
+        );
+        let tables = [(Path::new("a.csv"), first), (Path::new("b.csv"), second)];
+
+        let mut problems = Problems::default();
+        let records = merge(&tables, &mut problems);
+        let merged: Vec<_> = records.iter().map(|r| (r.id.as_str(), r.points)).collect();
+        assert_eq!(
+            merged,
+            [
+                ("1", Some(8.)),
+                ("2", Some(9.)),
+                ("5", Some(7.)),
+                ("6", Some(0.)),
+                ("4", None)
+            ]
+        );
+
+        let err = problems.finish().unwrap_err();
+        assert!(err
+            .to_string()
+            .ends_with("Tim (3): different points or feedback in a.csv, b.csv"));
+    }
+
+    #[test]
+    fn test_split() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let dir = tmp.path().to_path_buf();
+        let path = dir.join("submissions");
+        for (name, size) in [("Max_1", 300), ("Eva_2", 100), ("Tim_3", 100)] {
+            let folder = path.join(format!("{}{}", name, SUBMISSION_SUFFIX));
+            create_dir_all(&folder)?;
+            std::fs::write(folder.join("Main.java"), "x".repeat(size))?;
+        }
+        create_dir_all(path.join("notes"))?;
+        let tutors = ["anna".to_string(), "ben".to_string()];
+        let options = SplitOptions {
+            strategy: crate::tutors_split::Strategy::Size,
+            copy: true,
+            ..Default::default()
+        };
+
+        split(&path, &tutors, None, &options, false, false)?;
+
+        assert!(dir
+            .join("anna/Max_1_assignsubmission_file_/Main.java")
+            .is_file());
+        assert!(dir.join("ben/Eva_2_assignsubmission_file_").is_dir());
+        assert!(dir.join("ben/Tim_3_assignsubmission_file_").is_dir());
+        assert!(path.join("Max_1_assignsubmission_file_").is_dir());
+        assert!(!dir.join("anna/notes").exists() && !dir.join("ben/notes").exists());
+        let manifest = std::fs::read_to_string(dir.join("split.csv"))?;
+        assert_eq!(
+            manifest,
+            "Tutor,Folder,ID,Bytes\n\
+             ben,Eva_2_assignsubmission_file_,2,100\n\
+             anna,Max_1_assignsubmission_file_,1,300\n\
+             ben,Tim_3_assignsubmission_file_,3,100\n"
+        );
+
+        for tutors in [["anna", "anna"], ["anna", "../ben"]] {
+            let tutors = tutors.map(str::to_string);
+            assert!(split(&path, &tutors, None, &options, false, true).is_err());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_split() {
+        let manifest: Vec<_> = [("anna", "1"), ("anna", "2"), ("ben", "3"), ("ben", "4")]
+            .into_iter()
+            .map(|(tutor, id)| (tutor.to_string(), format!("F{}", id), id.to_string()))
+            .collect();
+        let anna = test_table(
+            "Teilnehmer/in1,Max,,,,\"8,00\",\"10,00\",Ja,,,\n\
+             Teilnehmer/in2,Eva,,,,\"9,00\",\"10,00\",Ja,,,\n\
+             Teilnehmer/in3,Tim,,,,\"0,00\",\"10,00\",Ja,,,Keine Abgabe.\n\
+             Teilnehmer/in4,Ida,,,,\"5,00\",\"10,00\",Ja,,,\n",
+        );
+        let ben = test_table("Teilnehmer/in5,Jan,,,,\"7,00\",\"10,00\",Ja,,,\n");
+        let tables = [(Path::new("anna.csv"), anna), (Path::new("ben.csv"), ben)];
+
+        assert_eq!(
+            check_split(&manifest, &tables),
+            [
+                (
+                    "anna.csv".to_string(),
+                    "Ida (4) is assigned to ben, not anna".to_string()
+                ),
+                (
+                    "ben.csv".to_string(),
+                    "Jan (5) is not in the split".to_string()
+                ),
+                ("ben".to_string(), "F3 is not graded".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fill_unmatched() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let dir = tmp.path().to_path_buf();
+        let records = test_table(
+            "Teilnehmer/in1,Max,,,,,\"10,00\",Ja,,,\n\
+             Teilnehmer/in2,Eva,,,,,\"10,00\",Ja,,,\n",
+        )
+        .records;
+        let dirs = HashMap::from([("1".to_string(), dir.clone())]);
+        let mut options = FillOptions {
+            feedback: None,
+            html: false,
+            unmatched: Unmatched::Drop,
+            late: None,
+            force: false,
+            ..Default::default()
+        };
+        let mut problems = Problems::default();
+
+        let filled = fill(
+            records.clone(),
+            &LOCALES[0],
+            &dirs,
+            &Grading::default(),
+            &options,
+            &mut problems,
+        );
+        assert_eq!(filled.len(), 1);
+        assert_eq!(filled[0].points, Some(10.));
+
+        options.unmatched = Unmatched::Keep;
+        let filled = fill(
+            records.clone(),
+            &LOCALES[0],
+            &dirs,
+            &Grading::default(),
+            &options,
+            &mut problems,
+        );
+        assert_eq!(filled[1], records[1]);
+
+        options.unmatched = Unmatched::Zero;
+        let filled = fill(
+            records.clone(),
+            &LOCALES[0],
+            &dirs,
+            &Grading::default(),
+            &options,
+            &mut problems,
+        );
+        assert_eq!(filled[1].points, Some(0.));
+        assert_eq!(filled[1].feedback, "Keine Abgabe.");
+
+        let filled = fill(
+            records.clone(),
+            &LOCALES[1],
+            &dirs,
+            &Grading::default(),
+            &options,
+            &mut problems,
+        );
+        assert_eq!(filled[1].feedback, "No submission.");
+        assert!(problems.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_fill_problem() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let dir = tmp.path().to_path_buf();
+        std::fs::write(dir.join("Main.java"), "// Tutor: good job\n")?;
+        let records = test_table("Teilnehmer/in1,Max,,,,,\"10,00\",Ja,,,\n").records;
+        let dirs = HashMap::from([("1".to_string(), dir.clone())]);
+        let options = FillOptions {
+            feedback: None,
+            html: false,
+            unmatched: Unmatched::Keep,
+            late: None,
+            force: false,
+            ..Default::default()
+        };
+        let mut problems = Problems::default();
+
+        let filled = fill(
+            records.clone(),
+            &LOCALES[0],
+            &dirs,
+            &Grading::default(),
+            &options,
+            &mut problems,
+        );
+        assert_eq!(filled, records);
+        assert!(!problems.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_fill_protected() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let dir = tmp.path().to_path_buf();
+        let records = test_table(
+            "Teilnehmer/in1,Max,,,,,\"10,00\",Nein,,,\n\
+             Teilnehmer/in2,Eva,,,,\"7,00\",\"10,00\",Ja,,,alt\n\
+             Teilnehmer/in3,Tim,,,,,\"10,00\",Ja,,,\n",
+        )
+        .records;
+        let dirs: HashMap<_, _> = ["1", "2", "3"]
+            .into_iter()
+            .map(|id| (id.to_string(), dir.clone()))
+            .collect();
+        let mut options = FillOptions {
+            feedback: None,
+            html: false,
+            unmatched: Unmatched::Drop,
+            late: None,
+            force: false,
+            ..Default::default()
+        };
+        let mut problems = Problems::default();
+
+        let filled = fill(
+            records.clone(),
+            &LOCALES[0],
+            &dirs,
+            &Grading::default(),
+            &options,
+            &mut problems,
+        );
+        assert_eq!(filled[0], records[0]);
+        assert_eq!(filled[1], records[1]);
+        assert_eq!(filled[2].points, Some(10.));
+
+        options.force = true;
+        let filled = fill(
+            records.clone(),
+            &LOCALES[0],
+            &dirs,
+            &Grading::default(),
+            &options,
+            &mut problems,
+        );
+        assert_eq!(filled[0], records[0]);
+        assert_eq!(filled[1].points, Some(10.));
+        assert!(problems.is_empty());
+        Ok(())
     }
-    fn test_stats() -> Result<()> {
-        todo!()
+
+    #[test]
+    fn test_fill_late() -> Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let dir = tmp.path().to_path_buf();
+        let records = test_table(
+            "Teilnehmer/in1,Max,,,,,\"10,00\",Ja,\"Mittwoch, 13. März 2024, 10:15\",,\n\
+             Teilnehmer/in2,Eva,,,,,\"10,00\",Ja,\"Freitag, 15. März 2024, 08:00\",,\n",
+        )
+        .records;
+        let dirs = HashMap::from([
+            ("1".to_string(), dir.clone()),
+            ("2".to_string(), dir.clone()),
+        ]);
+        let options = FillOptions {
+            feedback: None,
+            html: false,
+            unmatched: Unmatched::Drop,
+            late: Some(LatePolicy {
+                deadline: tutors_late::parse_date("2024-03-13 23:59").unwrap(),
+                penalty: 2.,
+                ..Default::default()
+            }),
+            force: false,
+            ..Default::default()
+        };
+        let mut problems = Problems::default();
+
+        let filled = fill(
+            records,
+            &LOCALES[0],
+            &dirs,
+            &Grading::default(),
+            &options,
+            &mut problems,
+        );
+        assert_eq!(filled[0].points, Some(10.));
+        assert_eq!(filled[0].feedback, "Bewertung siehe Feedbackdateien.");
+        assert_eq!(filled[1].points, Some(6.));
+        assert!(filled[1]
+            .feedback
+            .ends_with("\nVerspätete Abgabe (32:01 h): -4"));
+        Ok(())
     }
+
+    #[test]
     fn test_zipit() -> Result<()> {
-        todo!()
+        let tmp = tempfile::tempdir()?;
+        let dir = tmp.path().to_path_buf();
+        let submissions = dir.join("submissions");
+        let submission = submissions.join("Max_123_assignsubmission_file_");
+        create_dir_all(submission.join("src"))?;
+        std::fs::write(submission.join("src/Main.java"), "// Tutor: -1")?;
+        std::fs::write(submission.join("original.zip"), "")?;
+
+        zipit(
+            "feedback".to_string(),
+            &submissions,
+            None,
+            None,
+            false,
+            false,
+        )?;
+
+        assert!(!submission.join("src").exists());
+        assert!(submission.join("original.zip").is_file());
+        let inner = zip::ZipArchive::new(File::open(submission.join("feedback.zip"))?)?;
+        assert!(inner.file_names().any(|name| name == "src/Main.java"));
+
+        let outer = zip::ZipArchive::new(File::open(dir.join("feedbacks.zip"))?)?;
+        assert!(outer
+            .file_names()
+            .any(|name| name == "Max_123_assignsubmission_file_/feedback.zip"));
+        Ok(())
     }
 }